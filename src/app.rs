@@ -2,24 +2,48 @@ use std::{
     collections::{HashMap, HashSet},
     env, error,
     fmt::Display,
-    fs,
-    path::PathBuf,
+    fs, io,
+    path::{Path, PathBuf},
     process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use ratatui::layout::{Constraint, Direction, Layout, Rect, Size};
-
-use crate::{directory_entry::DirectoryEntry, input::Input, tui::Tui};
+use tokio::sync::mpsc;
+
+use crate::{
+    archive::{self, Archive},
+    buffer::{BufferCommand, DirBuffer},
+    config::{Config, Options, Snapshot},
+    details::Names,
+    directory_entry::{DirectoryEntry, Location},
+    du::{DirSize, DiskUsage, SizeCache},
+    event::Event,
+    finder::{Finder, FinderEvent},
+    fs_ops,
+    git::Git,
+    grep::{Grep, GrepEvent},
+    input::Input,
+    jobs::{JobEvent, JobId, JobQueue, JobTask},
+    journal::{Journal, Operation},
+    pdf,
+    preview::{Preview, Previewer},
+    register::{Register, RegisterKind},
+    search,
+    sorter::Sorter,
+    trash::{Trash, TrashView},
+    tui::Tui,
+    usage::{Usage, UsageEvent},
+    visibility::Visibility,
+    watcher::Watcher,
+};
 
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
-#[repr(u16)]
-enum DefaultConstraints {
-    Parent = 1,
-    Focus = 2,
-    Preview = 3,
-}
-
 #[derive(Debug)]
 pub struct AppCursor {
     pub entry: PathBuf,
@@ -63,32 +87,78 @@ pub struct App {
     pub focus_layout: Rect,
     pub preview_layout: Rect,
 
-    pub show_preview: bool,
+    pub options: Options,
+    pub config: Config,
 
     pub focus_dir: DirectoryEntry,
     pub parent_dir: Option<DirectoryEntry>,
-    pub preview: Option<PathBuf>,
+    pub preview: Previewer,
 
-    pub path_stack: Vec<PathBuf>,
+    pub path_stack: Vec<Location>,
     pub forward_stack: Vec<PathBuf>,
     pub selections: HashMap<PathBuf, HashSet<PathBuf>>, // TODO go back to hashMap so delete doesn't do bad things
     // pub cursor: Option<PathBuf>,
     // pub cursor_idx: usize,
     pub app_cursor: Option<AppCursor>,
-    pub wrap: bool,
 
     pub input: Option<Input>,
+
+    /// Last pattern typed at the search or filter prompt, highlighted in the
+    /// focus pane and used by n/N.
+    pub pattern: Option<String>,
+    pattern_backup: Option<String>,
+    filter_backup: Option<String>,
+    search_origin: usize,
+
+    pub trash: Option<Trash>,
+    pub trash_view: Option<TrashView>,
+    pub journal: Journal,
+
+    pub buffer: Option<DirBuffer>,
+    pub finder: Option<Finder>,
+    pub grep: Option<Grep>,
+    pub usage: Option<Usage>,
+    pub register: Register,
+
+    /// Feeds background work back into the main loop.
+    sender: mpsc::UnboundedSender<Event>,
+    pub jobs: JobQueue,
+    watcher: Watcher,
+    pub git: Git,
+    pub du: DiskUsage,
+    /// The listing `dir_sizes` last sized, so it's only done once.
+    sized: Option<PathBuf>,
+    /// Orders picked from the sort menu, by directory.
+    sort_memory: HashMap<PathBuf, Sorter>,
+    pub sort_menu: bool,
+    /// Shared with every listing, built from the options.
+    visibility: Arc<Visibility>,
+    /// Owners and groups for the detail columns.
+    pub names: Names,
+    pub jobs_view: Option<usize>,
+    reload_buffer_after: Option<JobId>,
+    /// The archive being listed to move into, dropped if the cursor leaves it.
+    opening: Option<(PathBuf, Arc<AtomicBool>)>,
+    /// Quits once the cancelled jobs have wound down.
+    quit_after_jobs: bool,
 }
 
 impl Default for App {
     fn default() -> Self {
         let curr_path = env::current_dir().expect("Couldn't read path");
-        let current_dir =
-            DirectoryEntry::new(curr_path.clone()).expect("Problem when creating parent directory");
+        let visibility = Arc::new(Visibility::default());
+        let sizes = Arc::new(SizeCache::default());
+        let current_dir = DirectoryEntry::new(
+            curr_path.clone(),
+            Sorter::default(),
+            visibility.clone(),
+            sizes.clone(),
+        )
+        .expect("Problem when creating parent directory");
 
         let app_cursor = match current_dir.contents.get(0) {
             Some(entry) => {
-                let entry_path = entry.to_path_buf();
+                let entry_path = entry.path.clone();
                 Some(AppCursor::new(entry_path, 0))
             }
             None => None,
@@ -96,7 +166,8 @@ impl Default for App {
 
         Self {
             area: Rect::default(),
-            show_preview: true,
+            options: Options::default(),
+            config: Config::default(),
             parent_constraint: Constraint::Fill(1),
             parent_needs_reset: false,
             focus_constraint: Constraint::Fill(2),
@@ -115,15 +186,20 @@ impl Default for App {
 
             parent_dir: match curr_path.parent() {
                 Some(parent) => Some(
-                    DirectoryEntry::new(parent.to_path_buf())
-                        .expect("Problem when creating directory"),
+                    DirectoryEntry::new(
+                        parent.to_path_buf(),
+                        Sorter::default(),
+                        visibility.clone(),
+                        sizes.clone(),
+                    )
+                    .expect("Problem when creating directory"),
                 ),
                 None => None,
             },
             path_stack: curr_path
                 .ancestors()
                 .skip(1) // skip current dir so path_stack.pop doesn't put me back in it
-                .map(|a| a.to_path_buf())
+                .map(|a| Location::Dir(a.to_path_buf()))
                 .collect::<Vec<_>>()
                 .into_iter()
                 .rev()
@@ -131,26 +207,314 @@ impl Default for App {
             forward_stack: Vec::new(),
             // cursor_idx: 0,
             focus_dir: current_dir,
-            preview: app_cursor.as_ref().map(|c| c.entry.clone()),
+            preview: Previewer::new(mpsc::unbounded_channel().0),
             app_cursor,
 
             selections: HashMap::new(),
-            wrap: true,
             input: None,
+
+            pattern: None,
+            pattern_backup: None,
+            filter_backup: None,
+            search_origin: 0,
+
+            trash: Trash::new(),
+            trash_view: None,
+            journal: Journal::new(),
+
+            buffer: None,
+            finder: None,
+            grep: None,
+            usage: None,
+            register: Register::new(),
+
+            // replaced by App::new, until then events from jobs go nowhere
+            sender: mpsc::unbounded_channel().0,
+            jobs: JobQueue::new(mpsc::unbounded_channel().0),
+            watcher: Watcher::new(mpsc::unbounded_channel().0),
+            git: Git::new(mpsc::unbounded_channel().0),
+            du: DiskUsage::new(mpsc::unbounded_channel().0, sizes),
+            sized: None,
+            sort_memory: HashMap::new(),
+            sort_menu: false,
+            visibility,
+            names: Names::default(),
+            jobs_view: None,
+            reload_buffer_after: None,
+            opening: None,
+            quit_after_jobs: false,
         }
     }
 }
 
 impl App {
     /// Constructs a new instance of [`App`].
-    pub fn new(size: Size) -> Self {
-        let mut app = Self::default();
-        let area = Rect::new(0, 0, size.width, size.height);
-        app.generate_layout(area);
-        app.area = area;
+    pub fn new(size: Size, sender: mpsc::UnboundedSender<Event>, config: Config) -> Self {
+        let mut app = Self {
+            jobs: JobQueue::new(sender.clone()),
+            preview: Previewer::new(sender.clone()),
+            watcher: Watcher::new(sender.clone()),
+            git: Git::new(sender.clone()),
+            sender,
+            message: config.error_message(),
+            config,
+            ..Self::default()
+        };
+        // the listings already look sizes up in this cache
+        app.du = DiskUsage::new(app.sender.clone(), app.du.cache.clone());
+        app.area = Rect::new(0, 0, size.width, size.height);
+        app.set_options(app.config.options());
         app
     }
 
+    /// Applies options coming from the config, re-sorting and re-laying out
+    /// only if needed.
+    pub fn set_options(&mut self, options: Options) {
+        let [parent, focus, preview] = options.ratios;
+        if self.parent_dir.is_some() {
+            self.parent_constraint = Constraint::Fill(parent);
+        }
+        self.focus_constraint = Constraint::Fill(focus);
+        self.preview_constraint = Constraint::Fill(if options.preview { preview } else { 0 });
+
+        if options.theme != self.options.theme {
+            if let Err(e) = self.preview.set_theme(&options.theme) {
+                self.message = Some(e);
+            }
+        }
+
+        let resort = options.sort != self.options.sort;
+        let refilter = options.hidden != self.options.hidden
+            || options.ignore != self.options.ignore
+            || options.gitignore != self.options.gitignore;
+        if refilter {
+            match Visibility::new(&options) {
+                Ok(visibility) => self.set_visibility(visibility),
+                Err(e) => self.message = Some(e),
+            }
+        }
+        self.options = options;
+        if resort {
+            // directories sorted from the menu keep their own order
+            for dir in self.parent_dir.iter_mut().chain([&mut self.focus_dir]) {
+                if !self.sort_memory.contains_key(&dir.path) {
+                    dir.sorter = self.options.sort;
+                    dir.resort();
+                }
+            }
+            self.sync_cursor();
+        }
+
+        self.generate_layout(self.area);
+    }
+
+    /// Picks up options changed from Lua.
+    pub fn sync_options(&mut self) {
+        let options = self.config.options();
+        if options != self.options {
+            self.set_options(options);
+        }
+    }
+
+    fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = Arc::new(visibility);
+        self.preview.set_visibility(self.visibility.clone());
+        for dir in self.parent_dir.iter_mut().chain([&mut self.focus_dir]) {
+            let _ = dir.set_visibility(self.visibility.clone());
+        }
+        self.sync_cursor();
+    }
+
+    pub fn toggle_hidden(&mut self) {
+        self.config.update_options(|o| o.hidden = !o.hidden);
+        self.sync_options();
+        let state = if self.options.hidden {
+            "Showing"
+        } else {
+            "Hiding"
+        };
+        self.message = Some(format!("{} hidden files", state));
+    }
+
+    pub fn toggle_details(&mut self) {
+        self.config.update_options(|o| o.details = !o.details);
+        self.sync_options();
+    }
+
+    pub fn toggle_preview(&mut self) {
+        self.config.update_options(|o| o.preview = !o.preview);
+        self.sync_options();
+    }
+
+    /// Pages through the PDF in the preview.
+    pub fn turn_page(&mut self, forward: bool) {
+        let turned = self
+            .app_cursor
+            .as_ref()
+            .filter(|c| pdf::is_pdf(&c.entry))
+            .is_some_and(|c| self.preview.turn_page(&c.entry, forward));
+        if !turned {
+            self.message = Some("No PDF to page through".to_string());
+        }
+    }
+
+    /// Keeps the preview in step with the cursor, or the selected grep hit,
+    /// called before every draw.
+    pub fn request_preview(&mut self) {
+        let area = self.preview_layout;
+        let size = (area.width, area.height);
+        if let Some(grep) = &self.grep {
+            match grep.selected_hit().filter(|_| area.width > 0) {
+                Some(hit) => self.preview.request_around(&hit.path, hit.line, size),
+                None => self.preview.cancel(),
+            }
+            return;
+        }
+        let Some(cursor) = self.app_cursor.as_ref().filter(|_| area.width > 0) else {
+            self.preview.cancel();
+            return;
+        };
+
+        let path = cursor.entry.clone();
+        let sort = self.sorter_for(&path);
+        match self.focus_dir.archive.clone() {
+            Some(archive) if archive.inner(&path).is_some() => {
+                self.preview.request_member(archive, &path, size, sort)
+            }
+            _ => self.preview.request(&path, size, sort),
+        }
+    }
+
+    pub fn handle_preview_ready(&mut self, preview: Preview) {
+        self.preview.handle_ready(preview);
+    }
+
+    /// The order `path` was last sorted in from the menu, or the configured
+    /// one.
+    pub fn sorter_for(&self, path: &Path) -> Sorter {
+        self.sort_memory
+            .get(path)
+            .copied()
+            .unwrap_or(self.options.sort)
+    }
+
+    pub fn open_sort_menu(&mut self) {
+        self.sort_menu = true;
+    }
+
+    pub fn close_sort_menu(&mut self) {
+        self.sort_menu = false;
+    }
+
+    /// Re-sorts the focus dir and remembers the order for it, the cursor
+    /// stays on the same entry.
+    pub fn set_sort(&mut self, sorter: Sorter) {
+        self.sort_memory.insert(self.focus_dir.path.clone(), sorter);
+        self.focus_dir.sorter = sorter;
+        self.focus_dir.resort();
+        self.sync_cursor();
+
+        let reversed = if sorter.reverse { ", reversed" } else { "" };
+        self.message = Some(format!("Sorted by {}{}", sorter.name(), reversed));
+    }
+
+    pub fn toggle_wrap(&mut self) {
+        self.config.update_options(|o| o.wrap = !o.wrap);
+        self.sync_options();
+        let state = if self.options.wrap { "on" } else { "off" };
+        self.message = Some(format!("Wrapping {}", state));
+    }
+
+    /// What Lua functions get to see through `fear.cwd()` and friends.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut selection: Vec<_> = self.current_selections().into_iter().collect();
+        selection.sort();
+
+        Snapshot {
+            cwd: self.focus_dir.path.clone(),
+            cursor: self.app_cursor.as_ref().map(|c| c.entry.clone()),
+            selection,
+        }
+    }
+
+    /// Jumps straight to `path` (relative to the focus dir), rebuilding the
+    /// stacks as if every directory on the way had been walked into.
+    pub fn change_dir(&mut self, path: PathBuf) {
+        let path = match fs::canonicalize(self.focus_dir.path.join(path)) {
+            Ok(path) => path,
+            Err(e) => {
+                self.message = Some(format!("Couldn't open directory: {}", e));
+                return;
+            }
+        };
+        let sorter = self.sorter_for(&path);
+        let focus_dir = match DirectoryEntry::new(
+            path.clone(),
+            sorter,
+            self.visibility.clone(),
+            self.du.cache.clone(),
+        ) {
+            Ok(dir) => dir,
+            Err(e) => {
+                self.message = Some(format!("Couldn't open {}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        self.parent_dir = path.parent().and_then(|p| {
+            DirectoryEntry::new(
+                p.to_path_buf(),
+                self.sorter_for(p),
+                self.visibility.clone(),
+                self.du.cache.clone(),
+            )
+            .ok()
+        });
+        self.path_stack = path
+            .ancestors()
+            .skip(1)
+            .map(|a| Location::Dir(a.to_path_buf()))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        self.forward_stack.clear();
+        self.app_cursor = focus_dir
+            .contents
+            .first()
+            .map(|entry| AppCursor::new(entry.path.clone(), 0));
+        self.focus_dir = focus_dir;
+
+        match self.parent_dir {
+            Some(_) => self.reset_parent_constraint(),
+            None => self.set_parent_constraint(0),
+        }
+        self.generate_layout(self.area);
+    }
+
+    /// Goes into `path` if it's a directory, otherwise to its parent with the
+    /// cursor on it.
+    pub fn navigate_to(&mut self, path: PathBuf) {
+        if path.is_dir() {
+            self.change_dir(path);
+            return;
+        }
+
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        self.change_dir(parent.to_path_buf());
+        // compare names only, the parent may have been canonicalized
+        let idx = self
+            .focus_dir
+            .contents
+            .iter()
+            .position(|e| e.path.file_name() == path.file_name());
+        if let Some(idx) = idx {
+            self.jump_to(idx);
+        }
+    }
+
     pub fn generate_layout(&mut self, area: Rect) {
         self.area = area;
         self.base_layout = Layout::default()
@@ -193,13 +557,136 @@ impl App {
     }
 
     /// Handles the tick event of the terminal.
-    pub fn tick(&mut self) {
+    /// Re-reads both panes.
+    pub fn refresh(&mut self) {
         let _ = match &mut self.parent_dir {
             Some(dir) => dir.update(),
             None => Ok(()),
         };
 
         let _ = self.focus_dir.update();
+        self.sync_cursor();
+    }
+
+    /// Watched directories are refreshed as they change, the rest get
+    /// re-read every `poll_interval`.
+    pub fn tick(&mut self) {
+        let interval = Duration::from_millis(self.options.poll_interval);
+        let mut updated = false;
+        for dir in self.parent_dir.iter_mut().chain([&mut self.focus_dir]) {
+            if self.watcher.is_polled(&dir.path) && dir.should_update(interval) {
+                let _ = dir.update();
+                updated = true;
+            }
+        }
+        if updated {
+            self.sync_cursor();
+        }
+    }
+
+    /// Follows the panes around, called before every draw. Archives have
+    /// nothing on disk to watch. The git directory is watched too, that's
+    /// where the index and HEAD change.
+    pub fn watch_dirs(&mut self) {
+        let dirs: Vec<&Path> = self
+            .parent_dir
+            .iter()
+            .chain([&self.focus_dir])
+            .filter(|d| d.archive.is_none())
+            .map(|d| d.path.as_path())
+            .chain(self.git.status.as_ref().map(|s| s.gitdir.as_path()))
+            .collect();
+        self.watcher.watch(&dirs);
+    }
+
+    /// Stops sizing a listing once it's left, and sizes new ones when
+    /// `dir_sizes` is on. Called before every draw.
+    pub fn follow_sizes(&mut self) {
+        let path = &self.focus_dir.path;
+        if self.du.dir().is_some_and(|dir| dir != path) {
+            self.du.cancel();
+        }
+        let auto = self.options.dir_sizes && self.focus_dir.archive.is_none();
+        if auto && self.sized.as_ref() != Some(path) {
+            self.sized = Some(path.clone());
+            self.du.compute(path, &self.focus_dir.contents, false);
+        }
+    }
+
+    /// Sizes the directories in the focus dir again, cached or not, or stops
+    /// if that's already going on.
+    pub fn size_dirs(&mut self) {
+        if self.focus_dir.archive.is_some() {
+            self.message = Some("Nothing to size in an archive".to_string());
+        } else if self.du.is_running() {
+            self.du.cancel();
+            self.message = Some("Stopped sizing directories".to_string());
+        } else {
+            let (dir, entries) = (&self.focus_dir.path, &self.focus_dir.contents);
+            self.du.compute(dir, entries, true);
+        }
+    }
+
+    pub fn handle_dir_size(&mut self, size: DirSize) {
+        self.du.handle_ready(&size);
+        for dir in self.parent_dir.iter_mut().chain([&mut self.focus_dir]) {
+            dir.set_dir_size(&size.path, size.size);
+        }
+        self.sync_cursor();
+    }
+
+    /// Keeps the git status on the repository the focus dir is in, called
+    /// before every draw.
+    pub fn follow_git(&mut self) {
+        if self.focus_dir.archive.is_none() {
+            self.git.follow(&self.focus_dir.path);
+        }
+    }
+
+    pub fn handle_fs_changed(&mut self, paths: HashSet<PathBuf>) {
+        let mut updated = false;
+        for dir in self.parent_dir.iter_mut().chain([&mut self.focus_dir]) {
+            if dir.archive.is_none() && paths.contains(&dir.path) {
+                let _ = dir.update();
+                updated = true;
+            }
+        }
+        if updated {
+            self.sync_cursor();
+            // sizes of whatever changed are gone from the cache
+            self.sized = None;
+        }
+        if paths.iter().any(|p| self.git.affected_by(p)) {
+            self.git.refresh();
+        }
+        if self
+            .app_cursor
+            .as_ref()
+            .is_some_and(|c| paths.contains(&c.entry))
+        {
+            self.preview.refresh();
+        }
+    }
+
+    /// Keeps the cursor on a valid entry after `focus_dir` was re-read,
+    /// following its path if it moved and clamping its index if it's gone.
+    pub fn sync_cursor(&mut self) {
+        let contents = &self.focus_dir.contents;
+        if contents.is_empty() {
+            self.app_cursor = None;
+            return;
+        }
+
+        let cursor = self
+            .app_cursor
+            .get_or_insert_with(|| AppCursor::new(contents[0].path.clone(), 0));
+        match contents.iter().position(|e| e.path == cursor.entry) {
+            Some(idx) => cursor.idx = idx,
+            None => {
+                cursor.idx = cursor.idx.min(contents.len() - 1);
+                cursor.entry = contents[cursor.idx].path.clone();
+            }
+        }
     }
 
     /// Set running to false to quit the application.
@@ -207,6 +694,27 @@ impl App {
         self.running = false;
     }
 
+    pub fn show_quit_msg(&mut self) {
+        self.message = Some(match self.jobs.jobs.len() {
+            1 => "A job is still running, cancel it and quit? [y/N]".to_string(),
+            n => format!("{} jobs are still running, cancel them and quit? [y/N]", n),
+        });
+    }
+
+    /// Jobs clean up after themselves when cancelled, quitting right away
+    /// would leave half a copy behind and hang on the runtime anyway.
+    pub fn cancel_jobs_and_quit(&mut self) {
+        if self.jobs.is_empty() {
+            self.quit();
+            return;
+        }
+        for job in &self.jobs.jobs {
+            self.jobs.cancel(job.id);
+        }
+        self.quit_after_jobs = true;
+        self.message = Some("Cancelling jobs before quitting...".to_string());
+    }
+
     // pub fn update(&mut self) {
     //     let _ = match &mut self.parent_dir {
     //         Some(dir) => dir.update(),
@@ -222,7 +730,7 @@ impl App {
                 app_cursor.idx = length - 1
             } else if app_cursor.idx > 0 {
                 app_cursor.idx -= 1
-            } else if self.wrap {
+            } else if self.options.wrap {
                 app_cursor.idx = length - 1
             } else {
                 return;
@@ -232,7 +740,7 @@ impl App {
                 .focus_dir
                 .contents
                 .get(app_cursor.idx)
-                .map(|path| path.to_path_buf())
+                .map(|entry| entry.path.clone())
                 .expect(&format!("{}", &app_cursor.idx.to_string()));
         }
     }
@@ -241,7 +749,7 @@ impl App {
         if let Some(app_cursor) = &mut self.app_cursor {
             if app_cursor.idx < self.focus_dir.contents.len() - 1 {
                 app_cursor.idx += 1;
-            } else if self.wrap {
+            } else if self.options.wrap {
                 app_cursor.idx = 0
             } else {
                 return;
@@ -251,32 +759,63 @@ impl App {
                 .focus_dir
                 .contents
                 .get(app_cursor.idx)
-                .map(|path| path.to_path_buf())
+                .map(|entry| entry.path.clone())
                 .expect("why?");
         }
     }
 
+    pub fn go_top(&mut self) {
+        self.jump_to(0);
+    }
+
+    pub fn go_bottom(&mut self) {
+        self.jump_to(self.focus_dir.contents.len().saturating_sub(1));
+    }
+
+    fn jump_to(&mut self, idx: usize) {
+        if let (Some(cursor), Some(entry)) =
+            (&mut self.app_cursor, self.focus_dir.contents.get(idx))
+        {
+            cursor.idx = idx;
+            cursor.entry = entry.path.clone();
+        }
+    }
+
     pub fn move_back(&mut self) {
-        if let Some(path) = self.path_stack.pop() {
+        if let Some(location) = self.path_stack.pop() {
             let focus_dir_path = self.focus_dir.path.clone();
 
-            self.focus_dir = DirectoryEntry::new(path).expect("Couldn't pop");
+            let sorter = self.sorter_for(&location.path());
+            self.focus_dir = DirectoryEntry::open(
+                location,
+                sorter,
+                self.visibility.clone(),
+                self.du.cache.clone(),
+            )
+            .expect("Couldn't pop");
 
             let cursor_idx = match &self.parent_dir {
                 Some(parent) => parent
                     .contents
                     .iter()
-                    .position(|p| p == &focus_dir_path)
+                    .position(|e| e.path == focus_dir_path)
                     .unwrap_or(0),
                 None => 0,
             };
 
             self.app_cursor = Some(AppCursor::new(focus_dir_path, cursor_idx));
 
-            self.parent_dir = match self.focus_dir.path.parent() {
+            // the stack holds every directory above the focus, which isn't
+            // always something `path.parent()` can be read from
+            self.parent_dir = match self.path_stack.last() {
                 Some(parent) => Some(
-                    DirectoryEntry::new(parent.to_path_buf())
-                        .expect("Problem when creating directory"),
+                    DirectoryEntry::open(
+                        parent.clone(),
+                        self.sorter_for(&parent.path()),
+                        self.visibility.clone(),
+                        self.du.cache.clone(),
+                    )
+                    .expect("Problem when creating directory"),
                 ),
                 None => {
                     self.set_parent_constraint(0);
@@ -287,7 +826,8 @@ impl App {
         };
     }
 
-    fn open_cursor(&mut self, entry: PathBuf) {
+    /// Opens `entry` in the editor, at `line` if given.
+    fn open_path(&mut self, entry: PathBuf, line: Option<usize>) {
         // TODO handle results
         use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 
@@ -295,6 +835,7 @@ impl App {
 
         #[cfg(target_os = "macos")]
         Command::new("nvim")
+            .args(line.map(|l| format!("+{}", l)))
             .arg(entry)
             .status()
             .expect("Failed to open nvim");
@@ -307,68 +848,146 @@ impl App {
 
         #[cfg(target_os = "linux")]
         Command::new("nvim")
+            .args(line.map(|l| format!("+{}", l)))
             .arg(entry)
             .status()
             .expect("Failed to open nvim");
 
         let _ = crossterm::execute!(std::io::stdout(), EnterAlternateScreen);
+        // whatever the editor left on screen isn't known to ratatui
+        self.needs_redraw = true;
     }
 
     pub fn move_into(&mut self) {
-        match &mut self.app_cursor {
-            Some(cursor) if cursor.entry.is_file() => {
-                let entry = cursor.entry.clone();
-                self.open_cursor(entry);
-                // todo open in nvim
-                return;
-            }
-            Some(cursor) => {
-                let new_focus_dir = match DirectoryEntry::new(cursor.entry.clone()) {
-                    Ok(dir) => dir,
-                    Err(_) => {
-                        // panic!("shit");
-                        return;
-                    }
-                };
+        let Some(cursor) = &self.app_cursor else {
+            return;
+        };
 
-                let cursor_idx = if let Some(_) = self.forward_stack.pop() {
-                    1
-                } else {
-                    0
-                };
-                let curr_dir = std::mem::take(&mut self.focus_dir);
-                self.parent_dir = Some(curr_dir.clone());
-                if self.parent_needs_reset {
-                    self.reset_parent_constraint();
-                    self.generate_layout(self.area);
+        if self.focus_dir.archive.is_none()
+            && archive::Format::detect(&cursor.entry).is_some()
+            && cursor.entry.is_file()
+        {
+            let path = cursor.entry.clone();
+            self.open_archive(path);
+        } else if cursor.entry.is_file() {
+            let entry = cursor.entry.clone();
+            self.open_path(entry, None);
+            // todo open in nvim
+        } else {
+            let location = match self.archive_member(&cursor.entry) {
+                Some((archive, inner)) if archive.is_dir(inner) => Location::Archive {
+                    archive: self.focus_dir.archive.clone().expect("checked above"),
+                    inner: inner.to_path_buf(),
+                },
+                Some(_) => {
+                    self.message = Some("Extract it first to open it (E)".to_string());
+                    return;
                 }
-                self.path_stack.push(curr_dir.path);
-                self.focus_dir = new_focus_dir;
+                None => Location::Dir(cursor.entry.clone()),
+            };
+            self.enter(location);
+        }
+    }
 
-                match self.focus_dir.contents.get(cursor_idx) {
-                    Some(c) => {
-                        let cursor_path = c.to_path_buf();
-                        // let first_entry = cursor_path
-                        //     .read_dir()
-                        //     .map(|entry| entry.map(|p| p.expect("W").path()))
-                        //     .expect(&format!("{}", cursor_path.display().to_string()))
-                        //     .collect();
+    /// Lists an archive on the blocking pool, a compressed tar has to be
+    /// decompressed to the end for that. Whatever was being listed before is
+    /// stopped.
+    fn open_archive(&mut self, path: PathBuf) {
+        if let Some((_, cancel)) = self.opening.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.opening = Some((path.clone(), cancel.clone()));
+        self.message = Some(format!("Listing {}...", fs_ops::display_name(&path)));
+
+        let sender = self.sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let archive = Archive::open(path.clone(), &cancel);
+            if !cancel.load(Ordering::Relaxed) {
+                let _ = sender.send(Event::ArchiveOpened(path, archive));
+            }
+        });
+    }
 
-                        // self.forward_stack.push(first_entry);
+    /// Moves into the archive if the cursor is still on it.
+    pub fn handle_archive_opened(&mut self, path: PathBuf, archive: io::Result<Archive>) {
+        if self.opening.as_ref().map(|(p, _)| p) != Some(&path) {
+            return;
+        }
+        self.opening = None;
+        self.message = None;
 
-                        // let mut contents = fs::read_dir(&path)?
-                        //         .map(|res| res.map(|e| e.path()))
-                        //         .collect::<Result<Vec<_>, io::Error>>()?;
+        let still_on_it = self.focus_dir.archive.is_none()
+            && self.app_cursor.as_ref().is_some_and(|c| c.entry == path);
+        match archive {
+            Ok(archive) if still_on_it => self.enter(Location::Archive {
+                archive: Arc::new(archive),
+                inner: PathBuf::new(),
+            }),
+            Ok(_) => {}
+            Err(e) => self.message = Some(format!("Couldn't open archive: {}", e)),
+        }
+    }
 
-                        self.app_cursor = Some(AppCursor::new(cursor_path, cursor_idx))
-                    }
-                    None => return,
-                }
+    /// Makes `location`, somewhere below the focus dir, the new focus dir.
+    fn enter(&mut self, location: Location) {
+        let sorter = self.sorter_for(&location.path());
+        let new_focus_dir = match DirectoryEntry::open(
+            location,
+            sorter,
+            self.visibility.clone(),
+            self.du.cache.clone(),
+        ) {
+            Ok(dir) => dir,
+            Err(_) => {
+                // panic!("shit");
+                return;
             }
-            None => return,
+        };
+
+        let cursor_idx = if self.forward_stack.pop().is_some() {
+            1
+        } else {
+            0
+        };
+        let curr_dir = std::mem::take(&mut self.focus_dir);
+        self.parent_dir = Some(curr_dir.clone());
+        if self.parent_needs_reset {
+            self.reset_parent_constraint();
+            self.generate_layout(self.area);
+        }
+        self.path_stack.push(curr_dir.location());
+        self.focus_dir = new_focus_dir;
+
+        if let Some(c) = self.focus_dir.contents.get(cursor_idx) {
+            let cursor_path = c.path.clone();
+            // let first_entry = cursor_path
+            //     .read_dir()
+            //     .map(|entry| entry.map(|p| p.expect("W").path()))
+            //     .expect(&format!("{}", cursor_path.display().to_string()))
+            //     .collect();
+
+            // self.forward_stack.push(first_entry);
+
+            // let mut contents = fs::read_dir(&path)?
+            //         .map(|res| res.map(|e| e.path()))
+            //         .collect::<Result<Vec<_>, io::Error>>()?;
+
+            self.app_cursor = Some(AppCursor::new(cursor_path, cursor_idx))
         }
     }
 
+    /// True when the focus dir is inside an archive.
+    pub fn in_archive(&self) -> bool {
+        self.focus_dir.archive.is_some()
+    }
+
+    /// The archive `path` is a member of, and its name in there.
+    pub fn archive_member<'a>(&'a self, path: &'a Path) -> Option<(&'a Archive, &'a Path)> {
+        let archive = self.focus_dir.archive.as_deref()?;
+        Some((archive, archive.inner(path)?))
+    }
+
     pub fn toggle_selection_on_cursor(&mut self) {
         let Some(cursor) = &self.app_cursor else {
             return;
@@ -395,31 +1014,255 @@ impl App {
             .unwrap_or_default()
     }
 
+    /// Marked entries of the focus directory, or the entry under the cursor
+    /// if nothing is marked.
+    pub fn selection_or_cursor(&self) -> Vec<PathBuf> {
+        // the disk usage view has a cursor of its own
+        if let Some(usage) = &self.usage {
+            return usage.selected_path().into_iter().collect();
+        }
+        match self.selections.get(&self.focus_dir.path) {
+            Some(selections) if !selections.is_empty() => selections.iter().cloned().collect(),
+            _ => self
+                .app_cursor
+                .as_ref()
+                .map(|c| vec![c.entry.clone()])
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Hands the selection (or cursor) over to a background job, unmarking
+    /// it since it's being taken care of.
+    fn spawn_on_selection_or_cursor<F>(&mut self, task: F)
+    where
+        F: FnOnce(Vec<PathBuf>) -> JobTask,
+    {
+        let paths = self.selection_or_cursor();
+        if paths.is_empty() {
+            return;
+        }
+
+        let selections = self.current_selections_mut();
+        for path in &paths {
+            selections.remove(path);
+        }
+        // the totals don't wait for the job
+        if let Some(usage) = &mut self.usage {
+            usage.remove_selected();
+        }
+        self.jobs.spawn(task(paths), self.trash.clone());
+    }
+
+    pub fn trash_selection_or_cursor(&mut self) {
+        if self.trash.is_none() {
+            self.message = Some("No trash directory available, is $HOME set?".to_string());
+            return;
+        }
+
+        self.spawn_on_selection_or_cursor(|paths| JobTask::Trash { paths });
+    }
+
     pub fn delete_selection_or_cursor(&mut self) {
-        // TODO some sort of trash bin to undo deletions?
-        if !self.selections.is_empty() {
-            if let Some(selections) = self.selections.get_mut(&self.focus_dir.path) {
-                selections.retain(|path| {
-                    match fs::remove_file(path).or_else(|_| fs::remove_dir_all(path)) {
-                        Ok(_) => false,
-                        Err(e) => {
-                            eprintln!("{}", e);
-                            true
-                        }
+        // permanent, so there's nothing for the journal to hold on to
+        self.spawn_on_selection_or_cursor(|paths| JobTask::Delete { paths });
+    }
+
+    /// Fills the register with everything marked, in any directory, or the
+    /// entry under the cursor if nothing is.
+    pub fn yank(&mut self, kind: RegisterKind) {
+        let mut paths: Vec<PathBuf> = self.selections.values().flatten().cloned().collect();
+        if paths.is_empty() {
+            paths.extend(self.app_cursor.as_ref().map(|c| c.entry.clone()));
+        }
+        if paths.is_empty() {
+            return;
+        }
+
+        self.message = Some(format!(
+            "{} {} entr{}",
+            match kind {
+                RegisterKind::Copy => "Yanked",
+                RegisterKind::Cut => "Cut",
+            },
+            paths.len(),
+            if paths.len() == 1 { "y" } else { "ies" }
+        ));
+        self.register.fill(paths, kind);
+        self.selections.clear();
+    }
+
+    pub fn paste(&mut self) {
+        if self.register.is_empty() {
+            self.message = Some("Nothing to paste".to_string());
+            return;
+        }
+
+        let sources = self.register.paths.clone();
+        let dest = self.focus_dir.path.clone();
+        let task = match self.register.kind {
+            RegisterKind::Copy => JobTask::Copy { sources, dest },
+            RegisterKind::Cut => {
+                // whatever was cut won't be where the register says anymore
+                self.register.clear();
+                JobTask::Move { sources, dest }
+            }
+        };
+
+        self.jobs.spawn(task, self.trash.clone());
+    }
+
+    /// Asks where to extract the marked members (or the one under the cursor)
+    /// to, next to the archive unless told otherwise.
+    pub fn show_extract_prompt(&mut self) -> bool {
+        let Some(archive) = &self.focus_dir.archive else {
+            self.message = Some("Not inside an archive".to_string());
+            return false;
+        };
+        let dest = archive
+            .path
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let Some(targets) = self.describe_selection_or_cursor() else {
+            return false;
+        };
+
+        self.message = Some(format!("Extract {} to: ", targets));
+        self.input = Some(Input {
+            char_idx: dest.chars().count(),
+            content: dest,
+        });
+        true
+    }
+
+    pub fn extract(&mut self) {
+        let Some(dest) = self.take_input().filter(|d| !d.is_empty()) else {
+            return;
+        };
+        let Some(archive) = self.focus_dir.archive.clone() else {
+            return;
+        };
+        // relative to where the archive is, that's what the prompt started at
+        let dest = archive.path.parent().unwrap_or(Path::new("")).join(dest);
+        if let Err(e) = fs::create_dir_all(&dest) {
+            self.message = Some(format!("Couldn't create {}: {}", dest.display(), e));
+            return;
+        }
+
+        let paths = self.selection_or_cursor();
+        let members: Vec<_> = paths
+            .iter()
+            .filter_map(|p| archive.inner(p))
+            .map(Path::to_path_buf)
+            .collect();
+        let selections = self.current_selections_mut();
+        for path in &paths {
+            selections.remove(path);
+        }
+
+        let task = JobTask::Extract {
+            archive: archive.path.clone(),
+            files: archive.files_under(&members),
+            members,
+            dest,
+        };
+        self.jobs.spawn(task, self.trash.clone());
+    }
+
+    pub fn handle_job_event(&mut self, event: JobEvent) {
+        match event {
+            JobEvent::Progress { id, progress } => self.jobs.update(id, progress),
+            JobEvent::Done {
+                id,
+                ops,
+                errors,
+                cancelled,
+            } => {
+                let Some(job) = self.jobs.finish(id) else {
+                    return;
+                };
+
+                let done = ops.len();
+                self.journal.record(ops);
+                self.refresh();
+
+                if self.reload_buffer_after == Some(id) {
+                    self.reload_buffer_after = None;
+                    if let Some(buffer) = &self.buffer {
+                        let row = buffer.row;
+                        self.buffer = Some(DirBuffer::new(
+                            self.focus_dir.path.clone(),
+                            &self.focus_dir.contents,
+                            row,
+                        ));
                     }
-                })
+                }
+
+                self.message = Some(match (cancelled, errors.as_slice()) {
+                    (true, _) => format!(
+                        "Cancelled {} {} after {} operations",
+                        job.verb.to_lowercase(),
+                        job.label,
+                        done
+                    ),
+                    (false, []) => format!("{} {}", job.past, job.label),
+                    (false, [error]) => error.clone(),
+                    (false, [error, rest @ ..]) => format!("{} (+{} more)", error, rest.len()),
+                });
+
+                if let Some(idx) = &mut self.jobs_view {
+                    *idx = (*idx).min(self.jobs.jobs.len().saturating_sub(1));
+                }
+                if self.quit_after_jobs && self.jobs.is_empty() {
+                    self.quit();
+                }
             }
-        } else if let Some(cursor) = &self.app_cursor {
-            let c_entry = cursor.entry.clone();
+        }
+    }
 
-            let _ = if cursor.entry.is_file() {
-                fs::remove_file(c_entry)
-            } else {
-                fs::remove_dir_all(c_entry)
-            };
+    pub fn open_jobs_view(&mut self) {
+        self.jobs_view = Some(0);
+    }
+
+    pub fn close_jobs_view(&mut self) {
+        self.jobs_view = None;
+    }
+
+    pub fn jobs_move_up(&mut self) {
+        if let Some(idx) = &mut self.jobs_view {
+            *idx = idx.saturating_sub(1);
         }
     }
 
+    pub fn jobs_move_down(&mut self) {
+        if let Some(idx) = &mut self.jobs_view {
+            *idx = (*idx + 1).min(self.jobs.jobs.len().saturating_sub(1));
+        }
+    }
+
+    pub fn cancel_selected_job(&mut self) {
+        let Some(job) = self.jobs_view.and_then(|idx| self.jobs.jobs.get(idx)) else {
+            return;
+        };
+
+        self.message = Some(format!(
+            "Cancelling {} {}",
+            job.verb.to_lowercase(),
+            job.label
+        ));
+        self.jobs.cancel(job.id);
+    }
+
+    pub fn undo(&mut self) {
+        self.message = Some(self.journal.undo(self.trash.as_ref()));
+        self.refresh();
+    }
+
+    pub fn redo(&mut self) {
+        self.message = Some(self.journal.redo(self.trash.as_ref()));
+        self.refresh();
+    }
+
     pub fn set_parent_constraint(&mut self, percent: u16) {
         self.parent_needs_reset = true;
         // self.parent_constraint = Constraint::Percentage(percent);
@@ -428,36 +1271,31 @@ impl App {
 
     pub fn reset_parent_constraint(&mut self) {
         // self.parent_constraint = Constraint::Percentage(DefaultConstraints::Parent as u16);
-        self.parent_constraint = Constraint::Fill(DefaultConstraints::Parent as u16);
+        self.parent_constraint = Constraint::Fill(self.options.ratios[0]);
     }
 
     // pub fn forward_path(&self) -> Option<&PathBuf> {
     //     self.forward_stack.last()
     // }
 
-    pub fn show_deletion_msg(&mut self) {
-        let selections_len = self
-            .selections
-            .get(&self.focus_dir.path)
-            .map_or(0, |s| s.len());
+    fn describe_selection_or_cursor(&self) -> Option<String> {
+        let targets = self.selection_or_cursor();
+        match targets.as_slice() {
+            [] => None,
+            [single] => Some(format!("\"{}\"", fs_ops::display_name(single))),
+            many => Some(format!("{} files", many.len())),
+        }
+    }
 
-        if selections_len != 0 {
-            self.message = Some(format!(
-                "Confirm deletion of {} files? [y/N]",
-                selections_len
-            ));
-            return;
+    pub fn show_trash_msg(&mut self) {
+        if let Some(targets) = self.describe_selection_or_cursor() {
+            self.message = Some(format!("Move {} to the trash? [y/N]", targets));
         }
+    }
 
-        if let Some(cursor) = &self.app_cursor {
-            self.message = Some(format!(
-                "Confirm deletion of \"{}\" [y/N]",
-                cursor
-                    .entry
-                    .file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy()
-            ));
+    pub fn show_deletion_msg(&mut self) {
+        if let Some(targets) = self.describe_selection_or_cursor() {
+            self.message = Some(format!("Permanently delete {}? [y/N]", targets));
         }
     }
 
@@ -507,6 +1345,157 @@ impl App {
         }
     }
 
+    pub fn show_search_prompt(&mut self) {
+        self.message = Some("/".to_string());
+        self.input = Some(Input::default());
+        self.pattern_backup = self.pattern.clone();
+        self.search_origin = self.app_cursor.as_ref().map_or(0, |c| c.idx);
+    }
+
+    /// Jumps to the first match at or after where the search started, as
+    /// the pattern is being typed.
+    pub fn update_search(&mut self) {
+        self.pattern = self
+            .input
+            .as_ref()
+            .map(|input| input.content.clone())
+            .filter(|p| !p.is_empty());
+
+        let idx = self
+            .find_match(self.search_origin, true)
+            .unwrap_or(self.search_origin);
+        self.jump_to(idx);
+    }
+
+    pub fn confirm_search(&mut self) {
+        self.input = None;
+        // an empty search repeats the last one, like vim
+        if self.pattern.is_none() {
+            self.pattern = self.pattern_backup.take();
+            self.search_next();
+        } else if self.find_match(self.search_origin, true).is_none() {
+            self.show_not_found();
+        }
+    }
+
+    pub fn cancel_search(&mut self) {
+        self.input = None;
+        self.pattern = self.pattern_backup.take();
+        self.jump_to(self.search_origin);
+    }
+
+    pub fn search_next(&mut self) {
+        self.search_step(true);
+    }
+
+    pub fn search_prev(&mut self) {
+        self.search_step(false);
+    }
+
+    fn search_step(&mut self, forward: bool) {
+        if self.pattern.is_none() {
+            self.message = Some("No previous pattern".to_string());
+            return;
+        }
+
+        let len = self.focus_dir.contents.len();
+        let Some(idx) = self.app_cursor.as_ref().map(|c| c.idx) else {
+            self.show_not_found();
+            return;
+        };
+        let start = if forward {
+            (idx + 1) % len
+        } else {
+            (idx + len - 1) % len
+        };
+        match self.find_match(start, forward) {
+            Some(idx) => self.jump_to(idx),
+            None => self.show_not_found(),
+        }
+    }
+
+    /// Index of the first entry matching the pattern, walking from `start`
+    /// in either direction and wrapping around.
+    fn find_match(&self, start: usize, forward: bool) -> Option<usize> {
+        let pattern = self.pattern.as_deref()?;
+        let contents = &self.focus_dir.contents;
+        let len = contents.len();
+
+        (0..len)
+            .map(|offset| {
+                if forward {
+                    (start + offset) % len
+                } else {
+                    (start + len - offset) % len
+                }
+            })
+            .find(|idx| {
+                let name = contents[*idx].name().to_string_lossy();
+                search::find(&name, pattern).is_some()
+            })
+    }
+
+    fn show_not_found(&mut self) {
+        if let Some(pattern) = &self.pattern {
+            self.message = Some(format!("Pattern not found: {}", pattern));
+        }
+    }
+
+    pub fn show_filter_prompt(&mut self) {
+        let filter = self.focus_dir.filter.clone().unwrap_or_default();
+        self.message = Some("Filter: ".to_string());
+        self.input = Some(Input {
+            char_idx: filter.chars().count(),
+            content: filter,
+        });
+        self.pattern_backup = self.pattern.clone();
+        self.filter_backup = self.focus_dir.filter.clone();
+    }
+
+    /// Hides non-matching entries of the focus dir as the filter is typed.
+    pub fn update_filter(&mut self) {
+        let filter = self
+            .input
+            .as_ref()
+            .map(|input| input.content.clone())
+            .filter(|f| !f.is_empty());
+        self.pattern = filter.clone();
+        self.set_filter(filter);
+    }
+
+    pub fn confirm_filter(&mut self) {
+        self.input = None;
+    }
+
+    pub fn cancel_filter(&mut self) {
+        self.input = None;
+        let filter = self.filter_backup.take();
+        self.set_filter(filter);
+        self.pattern = self.pattern_backup.take();
+    }
+
+    /// Drops the filter and the search highlight.
+    pub fn clear_search(&mut self) {
+        self.pattern = None;
+        if self.focus_dir.filter.is_some() {
+            self.set_filter(None);
+        }
+    }
+
+    fn set_filter(&mut self, filter: Option<String>) {
+        let _ = self.focus_dir.set_filter(filter);
+        self.sync_cursor();
+    }
+
+    pub fn show_command_prompt(&mut self) {
+        self.message = Some(":".to_string());
+        self.input = Some(Input::default());
+    }
+
+    pub fn take_input(&mut self) -> Option<String> {
+        self.input.take().map(|input| input.content)
+    }
+
     pub fn clear_msg(&mut self) {
         self.message = None;
     }
@@ -518,11 +1507,34 @@ impl App {
     }
 
     pub fn terminate_input(&mut self) {
-        if let Some(cursor) = &self.app_cursor {
-            if let Some(input) = &self.input {
-                let _ = fs::rename(&cursor.entry, &input.content);
-                self.input = None;
+        let Some(input) = self.input.take() else {
+            return;
+        };
+        let Some(cursor) = &self.app_cursor else {
+            return;
+        };
+        if input.content.is_empty() {
+            return;
+        }
+
+        // relative names are relative to the focus dir, not to wherever fear was started
+        let from = cursor.entry.clone();
+        let to = self.focus_dir.path.join(&input.content);
+        // kept its name, nothing to do
+        if to == from {
+            return;
+        }
+        if fs_ops::occupied(&to) {
+            self.message = Some(format!("{} already exists", to.display()));
+            return;
+        }
+
+        match fs::rename(&from, &to) {
+            Ok(_) => {
+                self.journal.record(vec![Operation::Move { from, to }]);
+                self.refresh();
             }
+            Err(e) => self.message = Some(format!("Couldn't rename: {}", e)),
         }
     }
 
@@ -531,4 +1543,289 @@ impl App {
             input.insert_char(ch);
         }
     }
+
+    pub fn open_trash_view(&mut self) -> bool {
+        let Some(trash) = &self.trash else {
+            self.message = Some("No trash directory available, is $HOME set?".to_string());
+            return false;
+        };
+
+        match trash.list() {
+            Ok(entries) => {
+                self.trash_view = Some(TrashView::new(entries));
+                true
+            }
+            Err(e) => {
+                self.message = Some(format!("Couldn't read the trash: {}", e));
+                false
+            }
+        }
+    }
+
+    pub fn close_trash_view(&mut self) {
+        self.trash_view = None;
+        self.refresh();
+    }
+
+    pub fn trash_move_up(&mut self) {
+        if let Some(view) = &mut self.trash_view {
+            view.move_up(self.options.wrap);
+        }
+    }
+
+    pub fn trash_move_down(&mut self) {
+        if let Some(view) = &mut self.trash_view {
+            view.move_down(self.options.wrap);
+        }
+    }
+
+    pub fn restore_trash_cursor(&mut self) {
+        self.apply_to_trash_cursor(|trash, entry| trash.restore(entry), "Restored");
+    }
+
+    pub fn purge_trash_cursor(&mut self) {
+        self.apply_to_trash_cursor(|trash, entry| trash.purge(entry), "Purged");
+    }
+
+    fn apply_to_trash_cursor<F>(&mut self, op: F, verb: &str)
+    where
+        F: FnOnce(&Trash, &crate::trash::TrashEntry) -> std::io::Result<()>,
+    {
+        let (Some(trash), Some(view)) = (&self.trash, &mut self.trash_view) else {
+            return;
+        };
+        let Some(entry) = view.selected() else {
+            return;
+        };
+
+        self.message = Some(match op(trash, entry) {
+            Ok(_) => {
+                let msg = format!("{} {}", verb, entry.original.display());
+                view.remove_selected();
+                msg
+            }
+            Err(e) => format!("{}: {}", entry.original.display(), e),
+        });
+    }
+
+    pub fn show_purge_msg(&mut self) {
+        if let Some(entry) = self.trash_view.as_ref().and_then(|v| v.selected()) {
+            self.message = Some(format!(
+                "Permanently delete \"{}\" from the trash? [y/N]",
+                entry.name.to_string_lossy()
+            ));
+        }
+    }
+
+    pub fn open_buffer(&mut self) {
+        let row = self.app_cursor.as_ref().map_or(0, |c| c.idx);
+        self.buffer = Some(DirBuffer::new(
+            self.focus_dir.path.clone(),
+            &self.focus_dir.contents,
+            row,
+        ));
+    }
+
+    /// Feeds a key to the directory buffer, returns false once it's closed.
+    pub fn buffer_key(&mut self, key_event: crossterm::event::KeyEvent) -> bool {
+        let Some(buffer) = &mut self.buffer else {
+            return false;
+        };
+
+        match buffer.handle_key(key_event) {
+            Some(BufferCommand::Write) => self.write_buffer(false),
+            Some(BufferCommand::WriteQuit) => self.write_buffer(true),
+            Some(BufferCommand::Quit) if buffer.is_modified() => {
+                self.message = Some("No write since last change (use :q! to discard)".to_string());
+            }
+            Some(BufferCommand::Quit) | Some(BufferCommand::ForceQuit) => self.close_buffer(),
+            None => {}
+        }
+
+        self.buffer.is_some()
+    }
+
+    fn write_buffer(&mut self, quit: bool) {
+        let Some(buffer) = &mut self.buffer else {
+            return;
+        };
+
+        match buffer.plan() {
+            Ok(plan) if plan.is_empty() => {
+                if quit {
+                    self.close_buffer();
+                } else {
+                    self.message = Some("No changes".to_string());
+                }
+            }
+            Ok(plan) => {
+                self.message = Some(format!(
+                    "Apply {} change{}? [y/N]",
+                    plan.len(),
+                    if plan.len() == 1 { "" } else { "s" }
+                ));
+                buffer.plan = Some(plan);
+                buffer.quit_after_apply = quit;
+            }
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    pub fn buffer_plan_pending(&self) -> bool {
+        self.buffer.as_ref().is_some_and(|b| b.plan.is_some())
+    }
+
+    pub fn apply_buffer_plan(&mut self) {
+        let Some(buffer) = &mut self.buffer else {
+            return;
+        };
+        let Some(plan) = buffer.plan.take() else {
+            return;
+        };
+        let quit = buffer.quit_after_apply;
+
+        let id = self
+            .jobs
+            .spawn(JobTask::Plan { changes: plan }, self.trash.clone());
+        self.clear_msg();
+
+        // reopened on the fresh listing once the job is done, so the ids
+        // match what's on disk again
+        if quit {
+            self.close_buffer();
+        } else {
+            self.reload_buffer_after = Some(id);
+        }
+    }
+
+    pub fn discard_buffer_plan(&mut self) {
+        if let Some(buffer) = &mut self.buffer {
+            buffer.plan = None;
+        }
+        self.clear_msg();
+    }
+
+    pub fn close_buffer(&mut self) {
+        self.buffer = None;
+        self.refresh();
+    }
+
+    pub fn open_finder(&mut self) {
+        self.finder = Some(Finder::new(
+            self.focus_dir.path.clone(),
+            self.sender.clone(),
+        ));
+    }
+
+    pub fn close_finder(&mut self) {
+        // dropping it stops the walk
+        self.finder = None;
+    }
+
+    pub fn handle_finder_event(&mut self, event: FinderEvent) {
+        if let Some(finder) = &mut self.finder {
+            finder.handle_event(event);
+        }
+    }
+
+    pub fn confirm_finder(&mut self) {
+        let path = self
+            .finder
+            .take()
+            .and_then(|finder| finder.selected_path().cloned());
+        if let Some(path) = path {
+            self.navigate_to(path);
+        }
+    }
+
+    pub fn show_grep_prompt(&mut self) {
+        self.message = Some("Grep: ".to_string());
+        self.input = Some(Input::default());
+    }
+
+    /// Greps under the focus dir for what was typed at the prompt, replacing
+    /// any previous results.
+    pub fn start_grep(&mut self) -> bool {
+        let Some(pattern) = self.take_input().filter(|p| !p.is_empty()) else {
+            return false;
+        };
+
+        match Grep::new(self.focus_dir.path.clone(), pattern, self.sender.clone()) {
+            Ok(grep) => {
+                self.grep = Some(grep);
+                true
+            }
+            Err(e) => {
+                self.message = Some(format!("Invalid pattern: {}", e));
+                false
+            }
+        }
+    }
+
+    pub fn handle_grep_event(&mut self, event: GrepEvent) {
+        if let Some(grep) = &mut self.grep {
+            grep.handle_event(event);
+        }
+    }
+
+    pub fn close_grep(&mut self) {
+        self.grep = None;
+    }
+
+    /// Scans the focus dir for the disk usage view.
+    pub fn open_usage(&mut self) -> bool {
+        if self.focus_dir.archive.is_some() {
+            self.message = Some("Nothing to scan in an archive".to_string());
+            return false;
+        }
+        self.usage = Some(Usage::new(self.focus_dir.path.clone(), self.sender.clone()));
+        true
+    }
+
+    pub fn handle_usage_event(&mut self, event: UsageEvent) {
+        if let Some(usage) = &mut self.usage {
+            usage.handle_event(event);
+        }
+    }
+
+    /// Stops the scan if it's still going, whatever was deleted is gone from
+    /// the panes too.
+    pub fn close_usage(&mut self) {
+        self.usage = None;
+        self.refresh();
+    }
+
+    pub fn usage_move_up(&mut self) {
+        if let Some(usage) = &mut self.usage {
+            usage.move_up(self.options.wrap);
+        }
+    }
+
+    pub fn usage_move_down(&mut self) {
+        if let Some(usage) = &mut self.usage {
+            usage.move_down(self.options.wrap);
+        }
+    }
+
+    pub fn usage_enter(&mut self) {
+        if let Some(usage) = &mut self.usage {
+            usage.enter();
+        }
+    }
+
+    pub fn usage_leave(&mut self) {
+        let Some(usage) = self.usage.as_mut().filter(|u| u.tree.is_some()) else {
+            return;
+        };
+        if !usage.leave() {
+            self.message = Some("That's where the scan started".to_string());
+        }
+    }
+
+    pub fn open_grep_hit(&mut self) {
+        let hit = self.grep.as_ref().and_then(|g| g.selected_hit().cloned());
+        if let Some(hit) = hit {
+            self.open_path(hit.path, Some(hit.line));
+        }
+    }
 }
@@ -0,0 +1,251 @@
+// archives browsed as if they were directories. Everything in one is listed
+// once on the blocking pool, when moving into it, and its members are then
+// shown as paths below the archive itself: `photos.zip/2024/beach.jpg`
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use flate2::read::MultiGzDecoder;
+use zip::ZipArchive;
+
+/// How much of a member is read for the preview.
+const HEAD_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl Format {
+    /// Goes by the name only, like most archivers do.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        [
+            (".zip", Format::Zip),
+            (".tar", Format::Tar),
+            (".tar.gz", Format::TarGz),
+            (".tgz", Format::TarGz),
+            (".tar.zst", Format::TarZst),
+            (".tzst", Format::TarZst),
+        ]
+        .into_iter()
+        .find(|(ext, _)| name.ends_with(ext))
+        .map(|(_, format)| format)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Member {
+    dir: bool,
+}
+
+#[derive(Debug)]
+pub struct Archive {
+    pub path: PathBuf,
+    format: Format,
+    /// Relative to the root, directories included even when the archive only
+    /// implies them.
+    members: BTreeMap<PathBuf, Member>,
+    /// The last member read for the preview. Getting to a member of a tar
+    /// means decompressing everything before it, so it's worth keeping.
+    head: Mutex<Option<(PathBuf, Arc<[u8]>)>>,
+}
+
+impl Archive {
+    /// Reads the whole listing, stopping early with an error once
+    /// `cancel` is set.
+    pub fn open(path: PathBuf, cancel: &AtomicBool) -> io::Result<Self> {
+        let format = Format::detect(&path).ok_or_else(not_an_archive)?;
+
+        let mut members = BTreeMap::new();
+        visit(&path, format, |name, dir, _| {
+            members.insert(name.to_path_buf(), Member { dir });
+            // not every archive has entries for the directories in it
+            for ancestor in name.ancestors().skip(1) {
+                if !ancestor.as_os_str().is_empty() {
+                    members.insert(ancestor.to_path_buf(), Member { dir: true });
+                }
+            }
+            Ok(!cancel.load(Ordering::Relaxed))
+        })?;
+        if cancel.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+
+        Ok(Self {
+            path,
+            format,
+            members,
+            head: Mutex::new(None),
+        })
+    }
+
+    /// The member a shown path stands for, empty for the root.
+    pub fn inner<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        path.strip_prefix(&self.path).ok()
+    }
+
+    pub fn is_dir(&self, inner: &Path) -> bool {
+        inner.as_os_str().is_empty() || self.members.get(inner).is_some_and(|m| m.dir)
+    }
+
+    /// Shown paths of what's directly inside `inner`, and whether they're
+    /// directories.
+    pub fn children(&self, inner: &Path) -> Vec<(PathBuf, bool)> {
+        self.members
+            .iter()
+            .filter(|(name, _)| name.parent() == Some(inner))
+            .map(|(name, member)| (self.path.join(name), member.dir))
+            .collect()
+    }
+
+    /// Files that extracting `inners` would write.
+    pub fn files_under(&self, inners: &[PathBuf]) -> u64 {
+        self.members
+            .iter()
+            .filter(|(name, member)| !member.dir && inners.iter().any(|i| name.starts_with(i)))
+            .count() as u64
+    }
+
+    /// The first few KiB of a member.
+    pub fn head(&self, inner: &Path) -> io::Result<Arc<[u8]>> {
+        if let Some((cached, bytes)) = &*self.head.lock().unwrap() {
+            if cached == inner {
+                return Ok(bytes.clone());
+            }
+        }
+
+        let mut bytes = Vec::new();
+        visit(&self.path, self.format, |name, _, reader| {
+            if name != inner {
+                return Ok(true);
+            }
+            reader.take(HEAD_SIZE).read_to_end(&mut bytes)?;
+            Ok(false)
+        })?;
+
+        let bytes: Arc<[u8]> = bytes.into();
+        *self.head.lock().unwrap() = Some((inner.to_path_buf(), bytes.clone()));
+        Ok(bytes)
+    }
+}
+
+/// Writes every member in `targets`, and everything below it, to the path
+/// it's paired with. `on_file` hears about every file written and can stop
+/// the whole thing by returning an error.
+pub fn extract<F>(path: &Path, targets: &[(PathBuf, PathBuf)], mut on_file: F) -> io::Result<()>
+where
+    F: FnMut(u64) -> io::Result<()>,
+{
+    let format = Format::detect(path).ok_or_else(not_an_archive)?;
+
+    visit(path, format, |name, dir, reader| {
+        let to = targets.iter().find_map(|(member, to)| {
+            let rest = name.strip_prefix(member).ok()?;
+            // joining an empty path would add a trailing slash
+            Some(if rest.as_os_str().is_empty() {
+                to.clone()
+            } else {
+                to.join(rest)
+            })
+        });
+        let Some(to) = to else {
+            return Ok(true);
+        };
+
+        if dir {
+            fs::create_dir_all(&to)?;
+            return Ok(true);
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let bytes = io::copy(reader, &mut File::create_new(&to)?)?;
+        on_file(bytes)?;
+        Ok(true)
+    })
+}
+
+/// Calls `f` with every member's name, whether it's a directory and its
+/// contents, until it returns false. Anything that isn't a plain file or a
+/// directory, like links, is skipped.
+fn visit<F>(path: &Path, format: Format, f: F) -> io::Result<()>
+where
+    F: FnMut(&Path, bool, &mut dyn Read) -> io::Result<bool>,
+{
+    let file = BufReader::new(File::open(path)?);
+    match format {
+        Format::Zip => visit_zip(file, f),
+        Format::Tar => visit_tar(file, f),
+        Format::TarGz => visit_tar(MultiGzDecoder::new(file), f),
+        Format::TarZst => visit_tar(zstd::Decoder::with_buffer(file)?, f),
+    }
+}
+
+fn visit_zip<F>(file: BufReader<File>, mut f: F) -> io::Result<()>
+where
+    F: FnMut(&Path, bool, &mut dyn Read) -> io::Result<bool>,
+{
+    let mut zip = ZipArchive::new(file)?;
+    for i in 0..zip.len() {
+        let mut member = zip.by_index(i)?;
+        let Some(name) = member.enclosed_name().as_deref().and_then(clean) else {
+            continue;
+        };
+        if !f(&name, member.is_dir(), &mut member)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn visit_tar<R, F>(reader: R, mut f: F) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(&Path, bool, &mut dyn Read) -> io::Result<bool>,
+{
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let kind = entry.header().entry_type();
+        if !kind.is_file() && !kind.is_dir() {
+            continue;
+        }
+        let Some(name) = clean(&entry.path()?) else {
+            continue;
+        };
+        if !f(&name, kind.is_dir(), &mut entry)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Member names as relative paths, `None` for anything that would end up
+/// outside of wherever it's extracted to.
+fn clean(name: &Path) -> Option<PathBuf> {
+    let mut cleaned = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => cleaned.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!cleaned.as_os_str().is_empty()).then_some(cleaned)
+}
+
+fn not_an_archive() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "not a supported archive")
+}
@@ -0,0 +1,946 @@
+// oil.nvim-like editing of a directory listing
+//
+// every line remembers which entry it came from (its id), so on save the
+// buffer can be diffed against the listing it was created from:
+//   - a line whose text changed is a rename/move of its entry
+//   - a second line with the same id (yy + p) is a copy
+//   - a line without an id (o, O, Enter...) creates a file, or a directory
+//     if it ends with '/'
+//   - ids that disappeared (dd, x...) are deleted, i.e. trashed
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    filesystem_entry::FileSystemEntry, fs_ops, input::Input, journal::Operation, trash::Trash,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferLine {
+    pub text: String,
+    id: Option<usize>,
+}
+
+impl BufferLine {
+    pub fn is_new(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferMode {
+    Normal,
+    Insert,
+    Command,
+}
+
+/// What the buffer asks of the app after handling a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferCommand {
+    Write,
+    WriteQuit,
+    Quit,
+    ForceQuit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Create { path: PathBuf, dir: bool },
+    Move { from: PathBuf, to: PathBuf },
+    Copy { from: PathBuf, to: PathBuf },
+    Delete { path: PathBuf },
+}
+
+impl Change {
+    pub fn describe(&self, base: &Path) -> String {
+        let rel = |p: &Path| p.strip_prefix(base).unwrap_or(p).display().to_string();
+        match self {
+            Change::Create { path, dir: true } => format!("CREATE {}/", rel(path)),
+            Change::Create { path, dir: false } => format!("CREATE {}", rel(path)),
+            Change::Move { from, to } => format!("MOVE   {} → {}", rel(from), rel(to)),
+            Change::Copy { from, to } => format!("COPY   {} → {}", rel(from), rel(to)),
+            Change::Delete { path } => format!("DELETE {}", rel(path)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    lines: Vec<BufferLine>,
+    row: usize,
+    col: usize,
+}
+
+#[derive(Debug)]
+pub struct DirBuffer {
+    pub dir: PathBuf,
+    /// Original path of every id.
+    originals: Vec<PathBuf>,
+    initial: Vec<BufferLine>,
+    pub lines: Vec<BufferLine>,
+    pub row: usize,
+    /// Char index into the current line.
+    pub col: usize,
+    pub mode: BufferMode,
+    pub command: Input,
+    /// Changes waiting for confirmation after a write.
+    pub plan: Option<Vec<Change>>,
+    pub quit_after_apply: bool,
+    pending: Option<char>,
+    register: Vec<BufferLine>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl DirBuffer {
+    pub fn new(dir: PathBuf, entries: &[FileSystemEntry], row: usize) -> Self {
+        let lines: Vec<BufferLine> = entries
+            .iter()
+            .enumerate()
+            .map(|(id, entry)| {
+                let mut text = fs_ops::display_name(&entry.path);
+                if entry.is_dir() {
+                    text.push('/');
+                }
+                BufferLine { text, id: Some(id) }
+            })
+            .collect();
+
+        Self {
+            dir,
+            originals: entries.iter().map(|e| e.path.clone()).collect(),
+            initial: lines.clone(),
+            row: row.min(lines.len().saturating_sub(1)),
+            lines,
+            col: 0,
+            mode: BufferMode::Normal,
+            command: Input::new(),
+            plan: None,
+            quit_after_apply: false,
+            pending: None,
+            register: Vec::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.lines != self.initial
+    }
+
+    /// True if the line differs from what was on disk when the buffer opened.
+    pub fn is_changed(&self, line: &BufferLine) -> bool {
+        match line.id {
+            Some(id) => self.initial[id].text != line.text,
+            None => true,
+        }
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines
+            .get(self.row)
+            .map_or(0, |l| l.text.chars().count())
+    }
+
+    fn chars(&self) -> Vec<char> {
+        self.lines
+            .get(self.row)
+            .map_or_else(Vec::new, |l| l.text.chars().collect())
+    }
+
+    fn byte_index(&self, col: usize) -> usize {
+        let text = &self.lines[self.row].text;
+        text.char_indices().nth(col).map_or(text.len(), |(i, _)| i)
+    }
+
+    fn clamp_col(&mut self) {
+        let len = self.line_len();
+        self.col = match self.mode {
+            BufferMode::Insert => self.col.min(len),
+            _ => self.col.min(len.saturating_sub(1)),
+        };
+    }
+
+    fn snapshot(&mut self) {
+        self.undo.push(Snapshot {
+            lines: self.lines.clone(),
+            row: self.row,
+            col: self.col,
+        });
+        self.redo.clear();
+    }
+
+    fn restore(&mut self, from_undo: bool) {
+        let (from, to) = if from_undo {
+            (&mut self.undo, &mut self.redo)
+        } else {
+            (&mut self.redo, &mut self.undo)
+        };
+
+        if let Some(snapshot) = from.pop() {
+            to.push(Snapshot {
+                lines: std::mem::replace(&mut self.lines, snapshot.lines),
+                row: self.row,
+                col: self.col,
+            });
+            self.row = snapshot.row;
+            self.col = snapshot.col;
+        }
+    }
+
+    // keeps at least one (empty, new) line around so there's always
+    // somewhere to put the cursor
+    fn ensure_line(&mut self) {
+        if self.lines.is_empty() {
+            self.lines.push(BufferLine {
+                text: String::new(),
+                id: None,
+            });
+        }
+        self.row = self.row.min(self.lines.len() - 1);
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<BufferCommand> {
+        match self.mode {
+            BufferMode::Insert => {
+                self.handle_insert(key);
+                None
+            }
+            BufferMode::Command => self.handle_command(key),
+            BufferMode::Normal => self.handle_normal(key),
+        }
+    }
+
+    fn handle_insert(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = BufferMode::Normal;
+                self.col = self.col.saturating_sub(1);
+                self.clamp_col();
+            }
+            KeyCode::Char(c) => {
+                self.ensure_line();
+                let idx = self.byte_index(self.col);
+                self.lines[self.row].text.insert(idx, c);
+                self.col += 1;
+            }
+            KeyCode::Backspace if self.col > 0 => {
+                let idx = self.byte_index(self.col - 1);
+                self.lines[self.row].text.remove(idx);
+                self.col -= 1;
+            }
+            KeyCode::Backspace if self.row > 0 => {
+                // joining drops the id of the joined line, like vim would
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.col = self.line_len();
+                self.lines[self.row].text.push_str(&line.text);
+            }
+            KeyCode::Enter => {
+                self.ensure_line();
+                let idx = self.byte_index(self.col);
+                let rest = self.lines[self.row].text.split_off(idx);
+                self.row += 1;
+                self.col = 0;
+                self.lines.insert(
+                    self.row,
+                    BufferLine {
+                        text: rest,
+                        id: None,
+                    },
+                );
+            }
+            KeyCode::Left => self.col = self.col.saturating_sub(1),
+            KeyCode::Right => self.col = (self.col + 1).min(self.line_len()),
+            KeyCode::Up => self.row = self.row.saturating_sub(1),
+            KeyCode::Down => self.row = (self.row + 1).min(self.lines.len().saturating_sub(1)),
+            _ => {}
+        }
+        self.clamp_col();
+    }
+
+    fn handle_command(&mut self, key: KeyEvent) -> Option<BufferCommand> {
+        match key.code {
+            KeyCode::Esc => self.mode = BufferMode::Normal,
+            KeyCode::Backspace if self.command.content.is_empty() => self.mode = BufferMode::Normal,
+            KeyCode::Backspace => self.command.delete_char(),
+            KeyCode::Left => self.command.move_cursor(false),
+            KeyCode::Right => self.command.move_cursor(true),
+            KeyCode::Char(c) => self.command.insert_char(c),
+            KeyCode::Enter => {
+                self.mode = BufferMode::Normal;
+                let cmd = std::mem::take(&mut self.command);
+                return match cmd.content.trim() {
+                    "w" => Some(BufferCommand::Write),
+                    "wq" | "x" => Some(BufferCommand::WriteQuit),
+                    "q" => Some(BufferCommand::Quit),
+                    "q!" => Some(BufferCommand::ForceQuit),
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_normal(&mut self, key: KeyEvent) -> Option<BufferCommand> {
+        if key.modifiers == KeyModifiers::CONTROL {
+            match key.code {
+                KeyCode::Char('s') => return Some(BufferCommand::Write),
+                KeyCode::Char('r') => self.restore(false),
+                _ => {}
+            }
+            self.clamp_col();
+            return None;
+        }
+
+        if let Some(op) = self.pending.take() {
+            self.handle_operator(op, key.code);
+            self.clamp_col();
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('h') | KeyCode::Left => self.col = self.col.saturating_sub(1),
+            KeyCode::Char('l') | KeyCode::Right => self.col += 1,
+            KeyCode::Char('k') | KeyCode::Up => self.row = self.row.saturating_sub(1),
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.row = (self.row + 1).min(self.lines.len().saturating_sub(1))
+            }
+            KeyCode::Char('w') => self.word_forward(),
+            KeyCode::Char('b') => self.word_backward(),
+            KeyCode::Char('e') => self.col = self.word_end(),
+            KeyCode::Char('0') => self.col = 0,
+            KeyCode::Char('^') => self.col = self.first_non_blank(),
+            KeyCode::Char('$') => self.col = usize::MAX,
+            KeyCode::Char('G') => self.row = self.lines.len().saturating_sub(1),
+            KeyCode::Char('x') => {
+                self.snapshot();
+                self.delete_range(self.col, self.col + 1);
+            }
+            KeyCode::Char('X') if self.col > 0 => {
+                self.snapshot();
+                self.delete_range(self.col - 1, self.col);
+                self.col -= 1;
+            }
+            KeyCode::Char('D') => {
+                self.snapshot();
+                self.delete_range(self.col, usize::MAX);
+            }
+            KeyCode::Char('C') => {
+                self.snapshot();
+                self.delete_range(self.col, usize::MAX);
+                self.enter_insert(usize::MAX);
+            }
+            KeyCode::Char('i') => self.insert_at(self.col),
+            KeyCode::Char('a') => self.insert_at(self.col + 1),
+            KeyCode::Char('I') => self.insert_at(self.first_non_blank()),
+            KeyCode::Char('A') => self.insert_at(usize::MAX),
+            KeyCode::Char('o') => self.open_line(self.row + 1),
+            KeyCode::Char('O') => self.open_line(self.row),
+            KeyCode::Char('p') => self.put(self.row + 1),
+            KeyCode::Char('P') => self.put(self.row),
+            KeyCode::Char('u') => self.restore(true),
+            KeyCode::Char(':') => {
+                self.command.reset();
+                self.mode = BufferMode::Command;
+            }
+            KeyCode::Char(op @ ('d' | 'c' | 'y' | 'g')) => self.pending = Some(op),
+            _ => {}
+        }
+
+        self.clamp_col();
+        None
+    }
+
+    fn handle_operator(&mut self, op: char, code: KeyCode) {
+        let KeyCode::Char(motion) = code else {
+            return;
+        };
+
+        if op == 'g' {
+            if motion == 'g' {
+                self.row = 0;
+            }
+            return;
+        }
+
+        // doubled operator works on the whole line
+        if motion == op {
+            match op {
+                'd' => self.delete_line(),
+                'y' => {
+                    self.register = self.lines.get(self.row).cloned().into_iter().collect();
+                }
+                'c' => {
+                    self.snapshot();
+                    self.delete_range(0, usize::MAX);
+                    self.enter_insert(0);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        let (start, end) = match motion {
+            // like vim, cw on a word only changes up to its end
+            'w' if op == 'c' => (self.col, self.current_word_end()),
+            'w' => (self.col, self.next_word_start()),
+            'e' => (self.col, self.word_end() + 1),
+            'b' => (self.prev_word_start(), self.col),
+            '$' => (self.col, usize::MAX),
+            '0' => (0, self.col),
+            _ => return,
+        };
+
+        match op {
+            'd' => {
+                self.snapshot();
+                self.delete_range(start, end);
+                self.col = start;
+            }
+            'c' => {
+                self.snapshot();
+                self.delete_range(start, end);
+                self.enter_insert(start);
+            }
+            'y' => {
+                let text: String = self.chars().iter().take(end).skip(start).collect();
+                self.register = vec![BufferLine { text, id: None }];
+            }
+            _ => {}
+        }
+    }
+
+    fn insert_at(&mut self, col: usize) {
+        self.snapshot();
+        self.enter_insert(col);
+    }
+
+    fn enter_insert(&mut self, col: usize) {
+        self.ensure_line();
+        self.mode = BufferMode::Insert;
+        self.col = col;
+        self.clamp_col();
+    }
+
+    fn open_line(&mut self, row: usize) {
+        self.snapshot();
+        let row = row.min(self.lines.len());
+        self.lines.insert(
+            row,
+            BufferLine {
+                text: String::new(),
+                id: None,
+            },
+        );
+        self.row = row;
+        self.enter_insert(0);
+    }
+
+    fn delete_line(&mut self) {
+        if self.row >= self.lines.len() {
+            return;
+        }
+        self.snapshot();
+        self.register = vec![self.lines.remove(self.row)];
+        self.row = self.row.min(self.lines.len().saturating_sub(1));
+    }
+
+    fn delete_range(&mut self, start: usize, end: usize) {
+        let chars = self.chars();
+        let end = end.min(chars.len());
+        if start >= end {
+            return;
+        }
+        self.lines[self.row].text = chars[..start].iter().chain(&chars[end..]).collect();
+    }
+
+    fn put(&mut self, row: usize) {
+        if self.register.is_empty() {
+            return;
+        }
+        self.snapshot();
+        let row = row.min(self.lines.len());
+        for (offset, line) in self.register.iter().enumerate() {
+            self.lines.insert(row + offset, line.clone());
+        }
+        self.row = row;
+        self.col = 0;
+    }
+
+    fn first_non_blank(&self) -> usize {
+        self.chars()
+            .iter()
+            .position(|c| !c.is_whitespace())
+            .unwrap_or(0)
+    }
+
+    fn next_word_start(&self) -> usize {
+        let chars = self.chars();
+        let mut col = self.col;
+        if let Some(&c) = chars.get(col) {
+            let class = char_class(c);
+            while chars.get(col).is_some_and(|&c| char_class(c) == class) {
+                col += 1;
+            }
+        }
+        while chars.get(col).is_some_and(|c| c.is_whitespace()) {
+            col += 1;
+        }
+        col
+    }
+
+    fn prev_word_start(&self) -> usize {
+        let chars = self.chars();
+        let mut col = self.col.min(chars.len());
+        while col > 0 && chars[col - 1].is_whitespace() {
+            col -= 1;
+        }
+        if col > 0 {
+            let class = char_class(chars[col - 1]);
+            while col > 0 && char_class(chars[col - 1]) == class {
+                col -= 1;
+            }
+        }
+        col
+    }
+
+    fn current_word_end(&self) -> usize {
+        let chars = self.chars();
+        let Some(&c) = chars.get(self.col) else {
+            return self.col;
+        };
+        let class = char_class(c);
+        let mut col = self.col;
+        while chars.get(col).is_some_and(|&c| char_class(c) == class) {
+            col += 1;
+        }
+        col
+    }
+
+    fn word_end(&self) -> usize {
+        let chars = self.chars();
+        let mut col = self.col + 1;
+        while chars.get(col).is_some_and(|c| c.is_whitespace()) {
+            col += 1;
+        }
+        if let Some(&c) = chars.get(col) {
+            let class = char_class(c);
+            while chars.get(col + 1).is_some_and(|&c| char_class(c) == class) {
+                col += 1;
+            }
+        }
+        col.min(chars.len().saturating_sub(1))
+    }
+
+    fn word_forward(&mut self) {
+        let col = self.next_word_start();
+        if col >= self.line_len() && self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.first_non_blank();
+        } else {
+            self.col = col;
+        }
+    }
+
+    fn word_backward(&mut self) {
+        if self.col == 0 && self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len();
+        }
+        self.col = self.prev_word_start();
+    }
+
+    /// Diffs the buffer against the listing it was opened from.
+    pub fn plan(&self) -> Result<Vec<Change>, String> {
+        let mut changes = Vec::new();
+        let mut seen_ids = HashSet::new();
+        let mut targets = HashSet::new();
+
+        // a line still carrying its original name is the entry itself, any
+        // other line with the same id becomes a copy
+        for line in &self.lines {
+            if let Some(id) = line.id {
+                if normalize(&self.dir.join(line.text.trim_end().trim_end_matches('/')))
+                    == self.originals[id]
+                {
+                    seen_ids.insert(id);
+                }
+            }
+        }
+        let kept = seen_ids.clone();
+
+        for line in &self.lines {
+            let text = line.text.trim_end();
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            let dir = text.ends_with('/');
+            let name = text.trim_end_matches('/');
+            if name.is_empty() || Path::new(name).is_absolute() {
+                return Err(format!("Invalid name \"{}\"", text));
+            }
+
+            // relative paths move things around, but never onto the
+            // directory itself or anything above it
+            let target = normalize(&self.dir.join(name));
+            if self.dir.starts_with(&target) {
+                return Err(format!("Invalid name \"{}\"", text));
+            }
+            if !targets.insert(target.clone()) {
+                return Err(format!("\"{}\" appears more than once", name));
+            }
+
+            changes.push(match line.id {
+                Some(id) if kept.contains(&id) && self.originals[id] == target => continue,
+                Some(id) if seen_ids.insert(id) => Change::Move {
+                    from: self.originals[id].clone(),
+                    to: target,
+                },
+                Some(id) => Change::Copy {
+                    from: self.originals[id].clone(),
+                    to: target,
+                },
+                None => Change::Create { path: target, dir },
+            });
+        }
+
+        for (id, path) in self.originals.iter().enumerate() {
+            if !seen_ids.contains(&id) {
+                changes.push(Change::Delete { path: path.clone() });
+            }
+        }
+
+        // anything that's going away frees up its name
+        let freed: HashSet<&PathBuf> = changes
+            .iter()
+            .filter_map(|c| match c {
+                Change::Move { from, .. } | Change::Delete { path: from } => Some(from),
+                _ => None,
+            })
+            .collect();
+
+        for change in &changes {
+            let target = match change {
+                Change::Create { path, .. } => path,
+                Change::Move { to, .. } | Change::Copy { to, .. } => to,
+                Change::Delete { .. } => continue,
+            };
+            if fs_ops::occupied(target) && !freed.contains(target) {
+                return Err(format!("{} already exists", target.display()));
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+// lexical, so "../foo" can move things up without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+type StepHook<'a> = &'a mut dyn FnMut() -> io::Result<()>;
+
+/// Runs a confirmed plan, returning what was done so it can be journaled
+/// even when something failed halfway through.
+///
+/// `on_step` runs before each change and can abort the rest by failing.
+pub fn execute(
+    plan: &[Change],
+    trash: Option<&Trash>,
+    on_step: StepHook,
+) -> (Vec<Operation>, Option<String>) {
+    let mut ops = Vec::new();
+    let res = execute_into(plan, trash, &mut ops, on_step);
+    (ops, res.err().map(|e| e.to_string()))
+}
+
+fn execute_into(
+    plan: &[Change],
+    trash: Option<&Trash>,
+    ops: &mut Vec<Operation>,
+    on_step: StepHook,
+) -> io::Result<()> {
+    // deletes first so their names are free, then directories so things
+    // can be moved into them, then everything else
+    for change in plan {
+        if let Change::Delete { path } = change {
+            on_step()?;
+            let trash = trash.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no trash directory available")
+            })?;
+            let entry = trash.put(path)?;
+            ops.push(Operation::Trash { entry });
+        }
+    }
+
+    for change in plan {
+        if let Change::Create { path, dir: true } = change {
+            on_step()?;
+            create_parents(path, ops)?;
+            if !fs_ops::occupied(path) {
+                fs::create_dir(path)?;
+                ops.push(Operation::Create {
+                    path: path.clone(),
+                    dir: true,
+                });
+            }
+        }
+    }
+
+    // moves happen in two phases so swaps (a -> b, b -> a) work out
+    let moves: Vec<(&PathBuf, &PathBuf)> = plan
+        .iter()
+        .filter_map(|c| match c {
+            Change::Move { from, to } => Some((from, to)),
+            _ => None,
+        })
+        .collect();
+    let sources: HashSet<&PathBuf> = moves.iter().map(|(from, _)| *from).collect();
+
+    let mut staged = Vec::new();
+    for (n, (from, to)) in moves.iter().enumerate() {
+        if sources.contains(to) {
+            let parent = from.parent().unwrap_or(Path::new("/"));
+            let tmp = fs_ops::unique_path(parent, format!(".fear-tmp-{}", n).as_ref());
+            fs_ops::move_path(from, &tmp)?;
+            ops.push(Operation::Move {
+                from: (*from).clone(),
+                to: tmp.clone(),
+            });
+            staged.push((tmp, *to));
+        } else {
+            staged.push(((*from).clone(), *to));
+        }
+    }
+
+    for (from, to) in staged {
+        on_step()?;
+        create_parents(to, ops)?;
+        fs_ops::move_path(&from, to)?;
+        ops.push(Operation::Move {
+            from,
+            to: to.clone(),
+        });
+    }
+
+    for change in plan {
+        match change {
+            Change::Copy { from, to } => {
+                on_step()?;
+                // the source may have been moved by this very plan
+                let from = moves
+                    .iter()
+                    .find(|(src, _)| *src == from)
+                    .map_or(from, |(_, dest)| *dest);
+                create_parents(to, ops)?;
+                fs_ops::copy_recursive(from, to)?;
+                ops.push(Operation::Copy {
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+            }
+            Change::Create { path, dir: false } => {
+                on_step()?;
+                create_parents(path, ops)?;
+                fs::File::create_new(path)?;
+                ops.push(Operation::Create {
+                    path: path.clone(),
+                    dir: false,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn create_parents(path: &Path, ops: &mut Vec<Operation>) -> io::Result<()> {
+    let missing: Vec<&Path> = path
+        .ancestors()
+        .skip(1)
+        .take_while(|p| !fs_ops::occupied(p))
+        .collect();
+
+    for dir in missing.into_iter().rev() {
+        fs::create_dir(dir)?;
+        ops.push(Operation::Create {
+            path: dir.to_path_buf(),
+            dir: true,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // nothing here exists, so plan() never finds a name taken on disk
+    fn buffer(names: &[&str]) -> DirBuffer {
+        let dir = PathBuf::from("/nonexistent/fear-buffer");
+        let entries: Vec<FileSystemEntry> = names
+            .iter()
+            .map(|name| FileSystemEntry::new(dir.join(name)))
+            .collect();
+        DirBuffer::new(dir, &entries, 0)
+    }
+
+    fn path(name: &str) -> PathBuf {
+        PathBuf::from("/nonexistent/fear-buffer").join(name)
+    }
+
+    #[test]
+    fn untouched_buffer_plans_nothing() {
+        assert_eq!(buffer(&["a", "b"]).plan(), Ok(vec![]));
+    }
+
+    #[test]
+    fn edited_line_is_a_rename() {
+        let mut buf = buffer(&["a", "b"]);
+        buf.lines[0].text = "c".into();
+        assert_eq!(
+            buf.plan(),
+            Ok(vec![Change::Move {
+                from: path("a"),
+                to: path("c")
+            }])
+        );
+    }
+
+    #[test]
+    fn swapped_names_are_two_renames() {
+        let mut buf = buffer(&["a", "b"]);
+        buf.lines[0].text = "b".into();
+        buf.lines[1].text = "a".into();
+        assert_eq!(
+            buf.plan(),
+            Ok(vec![
+                Change::Move {
+                    from: path("a"),
+                    to: path("b")
+                },
+                Change::Move {
+                    from: path("b"),
+                    to: path("a")
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn duplicated_line_is_a_copy() {
+        let mut buf = buffer(&["a"]);
+        let mut copy = buf.lines[0].clone();
+        copy.text = "a2".into();
+        buf.lines.push(copy);
+        assert_eq!(
+            buf.plan(),
+            Ok(vec![Change::Copy {
+                from: path("a"),
+                to: path("a2")
+            }])
+        );
+    }
+
+    #[test]
+    fn missing_line_is_a_delete_and_new_line_a_create() {
+        let mut buf = buffer(&["a", "b"]);
+        buf.lines.remove(1);
+        buf.lines.push(BufferLine {
+            text: "new/".into(),
+            id: None,
+        });
+        assert_eq!(
+            buf.plan(),
+            Ok(vec![
+                Change::Create {
+                    path: path("new"),
+                    dir: true
+                },
+                Change::Delete { path: path("b") },
+            ])
+        );
+    }
+
+    #[test]
+    fn repeated_names_are_rejected() {
+        let mut buf = buffer(&["a", "b"]);
+        buf.lines[1].text = "a".into();
+        assert!(buf.plan().is_err());
+    }
+
+    #[test]
+    fn relative_paths_move_but_never_above_the_directory() {
+        for name in ["..", ".", "sub/..", "../..", "/tmp/a"] {
+            let mut buf = buffer(&["a"]);
+            buf.lines[0].text = name.into();
+            assert!(buf.plan().is_err(), "{name} was accepted");
+        }
+
+        let mut buf = buffer(&["a"]);
+        buf.lines[0].text = "../a".into();
+        assert_eq!(
+            buf.plan(),
+            Ok(vec![Change::Move {
+                from: path("a"),
+                to: PathBuf::from("/nonexistent/a")
+            }])
+        );
+
+        let dir = std::env::temp_dir().join(format!("fear-buffer-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a"), "a").unwrap();
+
+        let mut buf = DirBuffer::new(dir.clone(), &[FileSystemEntry::new(dir.join("a"))], 0);
+        buf.lines[0].text = "sub/a".into();
+        let plan = buf.plan().unwrap();
+        assert_eq!(
+            plan,
+            [Change::Move {
+                from: dir.join("a"),
+                to: dir.join("sub/a")
+            }]
+        );
+
+        let (ops, err) = execute(&plan, None, &mut || Ok(()));
+        assert_eq!(err, None);
+        assert!(
+            matches!(&ops[0], Operation::Create { path, dir: true } if *path == dir.join("sub"))
+        );
+        assert_eq!(fs::read_to_string(dir.join("sub/a")).unwrap(), "a");
+        assert!(!dir.join("a").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+/// Built-in actions that keys, Lua and the `:` prompt can refer to by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Quit,
+    MoveUp,
+    MoveDown,
+    MoveInto,
+    MoveBack,
+    GoTop,
+    GoBottom,
+    Search,
+    SearchNext,
+    SearchPrev,
+    Filter,
+    ClearSearch,
+    Find,
+    Grep,
+    ToggleSelection,
+    Trash,
+    Delete,
+    DiskUsage,
+    Rename,
+    EditBuffer,
+    TrashView,
+    Jobs,
+    Yank,
+    Cut,
+    Paste,
+    Extract,
+    Undo,
+    Redo,
+    NextPage,
+    PrevPage,
+    SizeDirs,
+    SortMenu,
+    ToggleDetails,
+    ToggleHidden,
+    TogglePreview,
+    ToggleWrap,
+    CommandPrompt,
+}
+
+const NAMES: &[(&str, Command)] = &[
+    ("quit", Command::Quit),
+    ("move_up", Command::MoveUp),
+    ("move_down", Command::MoveDown),
+    ("move_into", Command::MoveInto),
+    ("move_back", Command::MoveBack),
+    ("go_top", Command::GoTop),
+    ("go_bottom", Command::GoBottom),
+    ("search", Command::Search),
+    ("search_next", Command::SearchNext),
+    ("search_prev", Command::SearchPrev),
+    ("filter", Command::Filter),
+    ("clear_search", Command::ClearSearch),
+    ("find", Command::Find),
+    ("grep", Command::Grep),
+    ("toggle_selection", Command::ToggleSelection),
+    ("trash", Command::Trash),
+    ("delete", Command::Delete),
+    ("disk_usage", Command::DiskUsage),
+    ("rename", Command::Rename),
+    ("edit_buffer", Command::EditBuffer),
+    ("trash_view", Command::TrashView),
+    ("jobs", Command::Jobs),
+    ("yank", Command::Yank),
+    ("cut", Command::Cut),
+    ("paste", Command::Paste),
+    ("extract", Command::Extract),
+    ("undo", Command::Undo),
+    ("redo", Command::Redo),
+    ("next_page", Command::NextPage),
+    ("prev_page", Command::PrevPage),
+    ("size_dirs", Command::SizeDirs),
+    ("sort_menu", Command::SortMenu),
+    ("toggle_details", Command::ToggleDetails),
+    ("toggle_hidden", Command::ToggleHidden),
+    ("toggle_preview", Command::TogglePreview),
+    ("toggle_wrap", Command::ToggleWrap),
+    ("command_prompt", Command::CommandPrompt),
+];
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, command)| *command)
+    }
+}
@@ -0,0 +1,526 @@
+use std::{cell::RefCell, collections::HashMap, env, fs, path::PathBuf, rc::Rc};
+
+use mlua::{Function, Lua, RegistryKey, Table, Value, Variadic};
+
+use crate::{
+    command::Command,
+    details::{self, Column},
+    highlight,
+    keymap::{self, Binding, KeyChord, Keymap, Lookup},
+    sorter::{self, Sorter},
+    visibility,
+};
+
+#[repr(u16)]
+enum DefaultConstraints {
+    Parent = 1,
+    Focus = 2,
+    Preview = 3,
+}
+
+/// Everything `fear.opt` can change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub wrap: bool,
+    pub preview: bool,
+    pub sort: Sorter,
+    /// Shows dotfiles and ignored entries.
+    pub hidden: bool,
+    /// Globs for names to leave out of listings, like `target` or `*.pyc`.
+    pub ignore: Vec<String>,
+    /// Also leaves out what `.gitignore` and `.ignore` files list.
+    pub gitignore: bool,
+    /// Shows `columns` next to the names in the focus pane.
+    pub details: bool,
+    /// The first ones are kept longest when the pane gets narrow.
+    pub columns: Vec<Column>,
+    /// `"relative"` or a strftime format for the mtime column.
+    pub time_format: String,
+    /// Sizes directories in the background as they're opened.
+    pub dir_sizes: bool,
+    /// Milliseconds between refreshes, only read at startup.
+    pub tick_rate: u64,
+    /// Milliseconds between re-reads of directories that can't be watched,
+    /// checked on every tick.
+    pub poll_interval: u64,
+    /// Relative widths of the parent, focus and preview panes.
+    pub ratios: [u16; 3],
+    /// Syntax highlighting theme for the preview, by name or `.tmTheme` path.
+    pub theme: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            wrap: true,
+            preview: true,
+            sort: Sorter::default(),
+            hidden: false,
+            ignore: Vec::new(),
+            gitignore: false,
+            details: false,
+            columns: vec![Column::Size, Column::Mtime, Column::Perms, Column::Owner],
+            time_format: "relative".to_string(),
+            dir_sizes: false,
+            tick_rate: 250,
+            poll_interval: 1000,
+            ratios: [
+                DefaultConstraints::Parent as u16,
+                DefaultConstraints::Focus as u16,
+                DefaultConstraints::Preview as u16,
+            ],
+            theme: highlight::DEFAULT_THEME.to_string(),
+        }
+    }
+}
+
+impl Options {
+    fn get<'lua>(&self, lua: &'lua Lua, name: &str) -> mlua::Result<Value<'lua>> {
+        let value = match name {
+            "wrap" => Value::Boolean(self.wrap),
+            "preview" => Value::Boolean(self.preview),
+            "sort" => Value::String(lua.create_string(self.sort.name())?),
+            "sort_reverse" => Value::Boolean(self.sort.reverse),
+            "hidden" => Value::Boolean(self.hidden),
+            "ignore" => Value::Table(lua.create_sequence_from(self.ignore.clone())?),
+            "gitignore" => Value::Boolean(self.gitignore),
+            "details" => Value::Boolean(self.details),
+            "columns" => {
+                Value::Table(lua.create_sequence_from(self.columns.iter().map(|c| c.name()))?)
+            }
+            "time_format" => Value::String(lua.create_string(&self.time_format)?),
+            "dir_sizes" => Value::Boolean(self.dir_sizes),
+            "tick_rate" => Value::Integer(self.tick_rate as i64),
+            "poll_interval" => Value::Integer(self.poll_interval as i64),
+            "ratios" => Value::Table(lua.create_sequence_from(self.ratios)?),
+            "theme" => Value::String(lua.create_string(&self.theme)?),
+            _ => return Err(unknown_option(name)),
+        };
+        Ok(value)
+    }
+
+    fn set(&mut self, name: &str, value: Value) -> mlua::Result<()> {
+        let invalid = |expected: &str| {
+            mlua::Error::RuntimeError(format!("fear.opt.{} expects {}", name, expected))
+        };
+
+        match name {
+            "wrap" => self.wrap = value.as_boolean().ok_or_else(|| invalid("a boolean"))?,
+            "preview" => self.preview = value.as_boolean().ok_or_else(|| invalid("a boolean"))?,
+            "sort" => {
+                let sort = value.as_str().and_then(Sorter::from_name).ok_or_else(|| {
+                    let names: Vec<_> = sorter::KEYS.iter().map(|(_, name, _)| *name).collect();
+                    invalid(&format!("one of {}", names.join(", ")))
+                })?;
+                self.sort.key = sort.key;
+            }
+            "sort_reverse" => {
+                self.sort.reverse = value.as_boolean().ok_or_else(|| invalid("a boolean"))?
+            }
+            "hidden" => self.hidden = value.as_boolean().ok_or_else(|| invalid("a boolean"))?,
+            "ignore" => {
+                let patterns = value
+                    .as_table()
+                    .and_then(|t| {
+                        t.clone()
+                            .sequence_values::<String>()
+                            .collect::<Result<Vec<_>, _>>()
+                            .ok()
+                    })
+                    .ok_or_else(|| invalid("a list of globs, e.g. { \"target\", \"*.pyc\" }"))?;
+                visibility::build_globs(&patterns).map_err(mlua::Error::RuntimeError)?;
+                self.ignore = patterns;
+            }
+            "gitignore" => {
+                self.gitignore = value.as_boolean().ok_or_else(|| invalid("a boolean"))?
+            }
+            "details" => self.details = value.as_boolean().ok_or_else(|| invalid("a boolean"))?,
+            "columns" => {
+                let columns = value
+                    .as_table()
+                    .and_then(|t| {
+                        t.clone()
+                            .sequence_values::<String>()
+                            .map(|name| name.ok().and_then(|n| Column::from_name(&n)))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .ok_or_else(|| {
+                        let names: Vec<_> =
+                            details::COLUMNS.iter().map(|(_, name)| *name).collect();
+                        invalid(&format!("a list of {}", names.join(", ")))
+                    })?;
+                self.columns = columns;
+            }
+            "time_format" => {
+                let format = value
+                    .as_str()
+                    .ok_or_else(|| invalid("\"relative\" or a strftime format"))?;
+                details::check_time_format(format).map_err(mlua::Error::RuntimeError)?;
+                self.time_format = format.to_string();
+            }
+            "dir_sizes" => {
+                self.dir_sizes = value.as_boolean().ok_or_else(|| invalid("a boolean"))?
+            }
+            "tick_rate" => {
+                self.tick_rate = value
+                    .as_integer()
+                    .and_then(|ms| u64::try_from(ms).ok())
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(|| invalid("a positive number of milliseconds"))?
+            }
+            "poll_interval" => {
+                self.poll_interval = value
+                    .as_integer()
+                    .and_then(|ms| u64::try_from(ms).ok())
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(|| invalid("a positive number of milliseconds"))?
+            }
+            "ratios" => {
+                let ratios = value
+                    .as_table()
+                    .and_then(|t| {
+                        t.clone()
+                            .sequence_values::<u16>()
+                            .collect::<Result<Vec<_>, _>>()
+                            .ok()
+                    })
+                    .and_then(|v| <[u16; 3]>::try_from(v).ok())
+                    .ok_or_else(|| invalid("three numbers, e.g. { 1, 2, 3 }"))?;
+                self.ratios = ratios;
+            }
+            "theme" => {
+                let theme = value.as_str().ok_or_else(|| invalid("a theme name"))?;
+                highlight::load_theme(theme).map_err(mlua::Error::RuntimeError)?;
+                self.theme = theme.to_string();
+            }
+            _ => return Err(unknown_option(name)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_option(name: &str) -> mlua::Error {
+    mlua::Error::RuntimeError(format!("unknown option \"{}\"", name))
+}
+
+/// Handle to a Lua function kept alive in the registry.
+#[derive(Debug, Clone, Copy)]
+pub struct LuaFn(usize);
+
+/// Things Lua asked the app to do. The app isn't reachable from inside Lua,
+/// so they are queued and applied once the call returns.
+#[derive(Debug)]
+pub enum Request {
+    Command(Command),
+    Cd(PathBuf),
+    Message(String),
+}
+
+/// What Lua can see of the app while one of its functions runs.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub cwd: PathBuf,
+    pub cursor: Option<PathBuf>,
+    pub selection: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    options: Options,
+    keymap: Keymap,
+    commands: HashMap<String, LuaFn>,
+    functions: Vec<RegistryKey>,
+    requests: Vec<Request>,
+    snapshot: Snapshot,
+}
+
+impl State {
+    fn keep(&mut self, lua: &Lua, function: Function) -> mlua::Result<LuaFn> {
+        self.functions.push(lua.create_registry_value(function)?);
+        Ok(LuaFn(self.functions.len() - 1))
+    }
+}
+
+/// The embedded Lua runtime and whatever the config files set up through it.
+#[derive(Debug)]
+pub struct Config {
+    lua: Lua,
+    state: Rc<RefCell<State>>,
+    /// Whatever went wrong while loading, shown once the UI is up.
+    pub errors: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let lua = Lua::new();
+        let state = Rc::new(RefCell::new(State::default()));
+        let errors = install_api(&lua, &state)
+            .err()
+            .map(|e| describe(&e))
+            .into_iter()
+            .collect();
+        Self { lua, state, errors }
+    }
+}
+
+impl Config {
+    /// Reads `keymap.toml` and then runs `init.lua`, both from
+    /// `$XDG_CONFIG_HOME/fear` (or `~/.config/fear`), so mappings made from
+    /// Lua win. A broken config is reported, not fatal: whatever was set
+    /// before an error stays set.
+    pub fn load() -> Self {
+        let mut config = Self::default();
+        config.state.borrow_mut().snapshot.cwd = env::current_dir().unwrap_or_default();
+        let Some(dir) = config_dir() else {
+            return config;
+        };
+
+        let keymap_path = dir.join("keymap.toml");
+        if keymap_path.exists() {
+            match fs::read_to_string(&keymap_path) {
+                Ok(source) => {
+                    let errors = config
+                        .state
+                        .borrow_mut()
+                        .keymap
+                        .load(&source, "keymap.toml");
+                    config.errors.extend(errors);
+                }
+                Err(e) => {
+                    config
+                        .errors
+                        .push(format!("Couldn't read {}: {}", keymap_path.display(), e))
+                }
+            }
+        }
+
+        let init_path = dir.join("init.lua");
+        if init_path.exists() {
+            let result = match fs::read_to_string(&init_path) {
+                Ok(source) => config
+                    .lua
+                    .load(source)
+                    .set_name(format!("@{}", init_path.display()))
+                    .exec()
+                    .map_err(|e| describe(&e)),
+                Err(e) => Err(format!("Couldn't read {}: {}", init_path.display(), e)),
+            };
+            config.errors.extend(result.err());
+        }
+
+        let shadowed = config.state.borrow().keymap.shadowed();
+        config.errors.extend(shadowed);
+        config
+    }
+
+    /// The load errors as a single line for the message bar.
+    pub fn error_message(&self) -> Option<String> {
+        match self.errors.as_slice() {
+            [] => None,
+            [error] => Some(error.clone()),
+            [error, rest @ ..] => Some(format!("{} (+{} more)", error, rest.len())),
+        }
+    }
+
+    pub fn options(&self) -> Options {
+        self.state.borrow().options.clone()
+    }
+
+    pub fn update_options<F>(&self, update: F)
+    where
+        F: FnOnce(&mut Options),
+    {
+        update(&mut self.state.borrow_mut().options);
+    }
+
+    pub fn lookup(&self, keys: &[KeyChord]) -> Lookup {
+        self.state.borrow().keymap.lookup(keys)
+    }
+
+    pub fn command(&self, name: &str) -> Option<LuaFn> {
+        self.state.borrow().commands.get(name).copied()
+    }
+
+    /// Requests queued outside of any call, i.e. while loading `init.lua`.
+    pub fn take_requests(&self) -> Vec<Request> {
+        std::mem::take(&mut self.state.borrow_mut().requests)
+    }
+
+    /// Calls a function from the config, errors come back as a message.
+    pub fn call(&self, function: LuaFn, snapshot: Snapshot, args: Option<String>) -> Vec<Request> {
+        self.state.borrow_mut().snapshot = snapshot;
+
+        // the key must be let go of before calling, the function will most
+        // likely borrow the state itself
+        let result = {
+            let state = self.state.borrow();
+            self.lua
+                .registry_value::<Function>(&state.functions[function.0])
+        }
+        .and_then(|f| f.call::<_, ()>(args));
+
+        let mut requests = self.take_requests();
+        if let Err(e) = result {
+            requests.push(Request::Message(describe(&e)));
+        }
+        requests
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("fear"))
+}
+
+/// First line of the error, without the traceback.
+fn describe(error: &mlua::Error) -> String {
+    let text = error.to_string();
+    let line = text.lines().next().unwrap_or_default();
+    line.strip_prefix("runtime error: ")
+        .unwrap_or(line)
+        .to_string()
+}
+
+fn install_api(lua: &Lua, state: &Rc<RefCell<State>>) -> mlua::Result<()> {
+    let fear = lua.create_table()?;
+
+    let opt = lua.create_table()?;
+    let meta = lua.create_table()?;
+    let s = state.clone();
+    meta.set(
+        "__index",
+        lua.create_function(move |lua, (_, name): (Table, String)| {
+            s.borrow().options.get(lua, &name)
+        })?,
+    )?;
+    let s = state.clone();
+    meta.set(
+        "__newindex",
+        lua.create_function(move |_, (_, name, value): (Table, String, Value)| {
+            s.borrow_mut().options.set(&name, value)
+        })?,
+    )?;
+    opt.set_metatable(Some(meta));
+    fear.set("opt", opt)?;
+
+    let s = state.clone();
+    fear.set(
+        "set",
+        lua.create_function(move |_, (name, value): (String, Value)| {
+            s.borrow_mut().options.set(&name, value)
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "map",
+        lua.create_function(move |lua, (key, action): (String, Value)| {
+            let keys = keymap::parse_sequence(&key).map_err(mlua::Error::RuntimeError)?;
+            let mut state = s.borrow_mut();
+            let binding = match action {
+                Value::String(name) => Binding::Command(command_named(name.to_str()?)?),
+                Value::Function(f) => Binding::Lua(state.keep(lua, f)?),
+                _ => {
+                    return Err(mlua::Error::RuntimeError(
+                        "fear.map expects an action name or a function".to_string(),
+                    ))
+                }
+            };
+            state.keymap.bind(keys, binding);
+            Ok(())
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "command",
+        lua.create_function(move |lua, (name, f): (String, Function)| {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "invalid command name \"{}\"",
+                    name
+                )));
+            }
+            let mut state = s.borrow_mut();
+            let f = state.keep(lua, f)?;
+            state.commands.insert(name, f);
+            Ok(())
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "action",
+        lua.create_function(move |_, name: String| {
+            let command = command_named(&name)?;
+            s.borrow_mut().requests.push(Request::Command(command));
+            Ok(())
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "cd",
+        lua.create_function(move |_, path: String| {
+            s.borrow_mut()
+                .requests
+                .push(Request::Cd(PathBuf::from(path)));
+            Ok(())
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "message",
+        lua.create_function(move |_, parts: Variadic<String>| {
+            let text = parts.join(" ");
+            s.borrow_mut().requests.push(Request::Message(text));
+            Ok(())
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "cwd",
+        lua.create_function(move |_, ()| {
+            Ok(s.borrow().snapshot.cwd.to_string_lossy().to_string())
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "cursor",
+        lua.create_function(move |_, ()| {
+            let state = s.borrow();
+            Ok(state
+                .snapshot
+                .cursor
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()))
+        })?,
+    )?;
+
+    let s = state.clone();
+    fear.set(
+        "selection",
+        lua.create_function(move |_, ()| {
+            let state = s.borrow();
+            Ok(state
+                .snapshot
+                .selection
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect::<Vec<_>>())
+        })?,
+    )?;
+
+    lua.globals().set("fear", fear)
+}
+
+fn command_named(name: &str) -> mlua::Result<Command> {
+    Command::from_name(name)
+        .ok_or_else(|| mlua::Error::RuntimeError(format!("unknown action \"{}\"", name)))
+}
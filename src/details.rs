@@ -0,0 +1,308 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+
+use crate::{filesystem_entry::FileSystemEntry, ui::human_size};
+
+/// Below this the name gets the whole line and columns are dropped.
+const NAME_MIN: usize = 16;
+/// Longer user and group names are cut.
+const OWNER_MAX: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Size,
+    Perms,
+    Owner,
+    Group,
+    Mtime,
+    Nlink,
+}
+
+/// Every column with what `fear.opt.columns` calls it.
+pub const COLUMNS: &[(Column, &str)] = &[
+    (Column::Size, "size"),
+    (Column::Perms, "perms"),
+    (Column::Owner, "owner"),
+    (Column::Group, "group"),
+    (Column::Mtime, "mtime"),
+    (Column::Nlink, "nlink"),
+];
+
+impl Column {
+    pub fn from_name(name: &str) -> Option<Self> {
+        COLUMNS.iter().find(|(_, n)| *n == name).map(|(c, _)| *c)
+    }
+
+    pub fn name(&self) -> &'static str {
+        COLUMNS
+            .iter()
+            .find(|(c, _)| c == self)
+            .map_or("size", |(_, name)| name)
+    }
+}
+
+/// `"relative"`, or anything strftime takes.
+pub fn check_time_format(format: &str) -> Result<(), String> {
+    if format == "relative" || !StrftimeItems::new(format).any(|i| i == Item::Error) {
+        Ok(())
+    } else {
+        Err(format!("\"{}\" isn't a valid time format", format))
+    }
+}
+
+/// User and group names by id, asking the system once for each.
+#[derive(Debug, Default)]
+pub struct Names {
+    users: RefCell<HashMap<u32, String>>,
+    groups: RefCell<HashMap<u32, String>>,
+}
+
+impl Names {
+    fn user(&self, uid: u32) -> String {
+        self.users
+            .borrow_mut()
+            .entry(uid)
+            .or_insert_with(|| match uzers::get_user_by_uid(uid) {
+                Some(user) => user.name().to_string_lossy().into_owned(),
+                None => uid.to_string(),
+            })
+            .clone()
+    }
+
+    fn group(&self, gid: u32) -> String {
+        self.groups
+            .borrow_mut()
+            .entry(gid)
+            .or_insert_with(|| match uzers::get_group_by_gid(gid) {
+                Some(group) => group.name().to_string_lossy().into_owned(),
+                None => gid.to_string(),
+            })
+            .clone()
+    }
+}
+
+/// The columns that fit next to the names of a listing, with their widths.
+#[derive(Debug)]
+pub struct Details<'a> {
+    columns: Vec<(Column, usize)>,
+    names: &'a Names,
+    time_format: &'a str,
+    now: SystemTime,
+}
+
+impl<'a> Details<'a> {
+    /// Widths come from the whole listing so they don't change while
+    /// scrolling. Columns listed last are the first to go when `width` is
+    /// too narrow for all of them.
+    pub fn fit(
+        columns: &[Column],
+        entries: &[FileSystemEntry],
+        width: usize,
+        names: &'a Names,
+        time_format: &'a str,
+    ) -> Self {
+        let mut details = Self {
+            columns: Vec::new(),
+            names,
+            time_format,
+            now: SystemTime::now(),
+        };
+        let mut used = NAME_MIN;
+        for column in columns {
+            let column_width = details.column_width(*column, entries);
+            // a space in front of each
+            if used + column_width + 1 > width {
+                break;
+            }
+            used += column_width + 1;
+            details.columns.push((*column, column_width));
+        }
+        details
+    }
+
+    /// Everything after the name.
+    pub fn width(&self) -> usize {
+        self.columns.iter().map(|(_, w)| w + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    pub fn cells(&self, entry: &FileSystemEntry) -> String {
+        let mut line = String::new();
+        for (column, width) in &self.columns {
+            let cell = self.cell(*column, entry);
+            match column {
+                Column::Size | Column::Nlink | Column::Mtime => {
+                    line += &format!(" {:>width$}", cell)
+                }
+                _ => line += &format!(" {:<width$}", cell),
+            }
+        }
+        line
+    }
+
+    fn column_width(&self, column: Column, entries: &[FileSystemEntry]) -> usize {
+        match column {
+            // "1023.9 KiB"
+            Column::Size => 10,
+            Column::Perms => 10,
+            // by id, there are usually only a couple in a directory
+            Column::Owner => {
+                let uids: HashSet<_> = entries.iter().map(|e| e.uid).collect();
+                let widths = uids
+                    .into_iter()
+                    .map(|uid| self.names.user(uid).chars().count());
+                widths.max().unwrap_or(1).clamp(1, OWNER_MAX)
+            }
+            Column::Group => {
+                let gids: HashSet<_> = entries.iter().map(|e| e.gid).collect();
+                let widths = gids
+                    .into_iter()
+                    .map(|gid| self.names.group(gid).chars().count());
+                widths.max().unwrap_or(1).clamp(1, OWNER_MAX)
+            }
+            Column::Mtime if self.time_format == "relative" => 4,
+            Column::Mtime => self.time(self.now).chars().count(),
+            Column::Nlink => entries
+                .iter()
+                .map(|e| e.nlink)
+                .max()
+                .map_or(1, |n| n.to_string().len()),
+        }
+    }
+
+    fn cell(&self, column: Column, entry: &FileSystemEntry) -> String {
+        // archive members and whatever couldn't be stat'ed
+        if !entry.has_metadata() {
+            return "-".to_string();
+        }
+        match column {
+            Column::Size => entry.total_size().map_or("-".to_string(), human_size),
+            Column::Perms => permissions(entry.mode),
+            Column::Owner => self.names.user(entry.uid).chars().take(OWNER_MAX).collect(),
+            Column::Group => self
+                .names
+                .group(entry.gid)
+                .chars()
+                .take(OWNER_MAX)
+                .collect(),
+            Column::Mtime => entry.modified.map_or("-".to_string(), |t| self.time(t)),
+            Column::Nlink => entry.nlink.to_string(),
+        }
+    }
+
+    fn time(&self, time: SystemTime) -> String {
+        match self.time_format {
+            "relative" => relative(self.now.duration_since(time).unwrap_or_default()),
+            format => DateTime::<Local>::from(time).format(format).to_string(),
+        }
+    }
+}
+
+/// Like `ls -l`, `drwxr-xr-x`.
+pub fn permissions(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o010000 => 'p',
+        0o140000 => 's',
+        0o060000 => 'b',
+        0o020000 => 'c',
+        _ => '-',
+    };
+
+    let mut perms = String::from(kind);
+    // setuid, setgid and sticky replace the x of their triplet
+    let special = [(0o4000, 's'), (0o2000, 's'), (0o1000, 't')];
+    for (triplet, (bit, letter)) in special.into_iter().enumerate() {
+        let shift = 6 - triplet * 3;
+        let rwx = (mode >> shift) & 0o7;
+        perms.push(if rwx & 4 != 0 { 'r' } else { '-' });
+        perms.push(if rwx & 2 != 0 { 'w' } else { '-' });
+        perms.push(match (rwx & 1 != 0, mode & bit != 0) {
+            (true, true) => letter,
+            (false, true) => letter.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    perms
+}
+
+/// At most four wide, `59s`, `12m`, `23h`, `6d`, `11mo`, `3y`.
+fn relative(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        86400..=2591999 => format!("{}d", secs / 86400),
+        2592000..=31535999 => format!("{}mo", secs / 2592000),
+        _ => format!("{}y", secs / 31536000),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permissions_look_like_ls() {
+        assert_eq!(permissions(0o040755), "drwxr-xr-x");
+        assert_eq!(permissions(0o100644), "-rw-r--r--");
+        assert_eq!(permissions(0o120777), "lrwxrwxrwx");
+        assert_eq!(permissions(0o010600), "prw-------");
+        assert_eq!(permissions(0o140000), "s---------");
+        assert_eq!(permissions(0o060660), "brw-rw----");
+        assert_eq!(permissions(0o020620), "crw--w----");
+    }
+
+    #[test]
+    fn special_bits_replace_the_x() {
+        assert_eq!(permissions(0o104755), "-rwsr-xr-x");
+        assert_eq!(permissions(0o102755), "-rwxr-sr-x");
+        assert_eq!(permissions(0o041777), "drwxrwxrwt");
+        // without the x underneath they're shown in capitals
+        assert_eq!(permissions(0o107644), "-rwSr-Sr-T");
+    }
+
+    #[test]
+    fn relative_ages_pick_the_biggest_unit() {
+        let ages = [
+            (0, "0s"),
+            (59, "59s"),
+            (60, "1m"),
+            (3599, "59m"),
+            (3600, "1h"),
+            (86399, "23h"),
+            (86400, "1d"),
+            (2591999, "29d"),
+            (2592000, "1mo"),
+            (31535999, "12mo"),
+            (31536000, "1y"),
+            (99 * 31536000, "99y"),
+        ];
+        for (secs, expected) in ages {
+            let age = relative(Duration::from_secs(secs));
+            assert_eq!(age, expected);
+            assert!(age.len() <= 4);
+        }
+    }
+
+    #[test]
+    fn time_formats_are_checked() {
+        assert!(check_time_format("relative").is_ok());
+        assert!(check_time_format("%Y-%m-%d %H:%M").is_ok());
+        assert!(check_time_format("%Q").is_err());
+    }
+}
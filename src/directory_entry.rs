@@ -1,22 +1,69 @@
 use std::{
     fs, io,
     path::{Path, PathBuf},
-    time::Instant,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::sorter::Sorter;
+use crate::{
+    archive::Archive,
+    du::SizeCache,
+    file_entry::FileKind,
+    filesystem_entry::FileSystemEntry,
+    search,
+    sorter::{SortKey, Sorter},
+    visibility::Visibility,
+};
+
+/// Where a listing comes from, a real directory or one inside an archive.
+#[derive(Debug, Clone)]
+pub enum Location {
+    Dir(PathBuf),
+    /// `inner` is relative to the archive's root, empty for the root itself.
+    Archive {
+        archive: Arc<Archive>,
+        inner: PathBuf,
+    },
+}
+
+impl Location {
+    /// What a listing of it is shown as.
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Location::Dir(path) => path.clone(),
+            Location::Archive { archive, inner } => Self::archive_path(archive, inner),
+        }
+    }
+
+    fn archive_path(archive: &Archive, inner: &Path) -> PathBuf {
+        // joining an empty path would leave a trailing slash
+        if inner.as_os_str().is_empty() {
+            archive.path.clone()
+        } else {
+            archive.path.join(inner)
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct DirectoryEntry {
     pub path: PathBuf,
     pub parent: Option<PathBuf>,
-    pub contents: Vec<PathBuf>,
+    pub contents: Vec<FileSystemEntry>,
     pub cursor_idx: usize,
+    pub sorter: Sorter,
+    pub visibility: Arc<Visibility>,
+    /// How many entries `visibility` left out, the filter doesn't count.
+    pub hidden: usize,
+    /// Where directory sizes computed so far are looked up.
+    pub sizes: Arc<SizeCache>,
+    /// Only entries matching this are kept in `contents`.
+    pub filter: Option<String>,
+    /// Set when listing the inside of an archive, `path` is then made up.
+    pub archive: Option<Arc<Archive>>,
     last_update: Instant,
 }
 
-const SORTER: Sorter = Sorter::DirsFirst;
-
 impl Default for DirectoryEntry {
     fn default() -> Self {
         Self {
@@ -24,42 +71,135 @@ impl Default for DirectoryEntry {
             parent: None,
             contents: Vec::new(),
             cursor_idx: usize::default(),
+            sorter: Sorter::default(),
+            visibility: Arc::default(),
+            hidden: 0,
+            sizes: Arc::default(),
+            filter: None,
+            archive: None,
             last_update: Instant::now(),
         }
     }
 }
 
 impl DirectoryEntry {
-    pub fn new(path: PathBuf) -> io::Result<Self> {
-        let mut contents = fs::read_dir(&path)?
-                .map(|res| res.map(|e| e.path()))
-                .collect::<Result<Vec<_>, io::Error>>()?;
-        contents.sort_by(|a, b| SORTER.paths(a, b));
-
-        Ok(Self {
-            contents,
+    pub fn new(
+        path: PathBuf,
+        sorter: Sorter,
+        visibility: Arc<Visibility>,
+        sizes: Arc<SizeCache>,
+    ) -> io::Result<Self> {
+        let mut dir = Self {
             parent: path.parent().map(Path::to_path_buf),
             path,
-            cursor_idx: 0,
-            last_update: Instant::now(),
-        })
+            sorter,
+            visibility,
+            sizes,
+            ..Self::default()
+        };
+        dir.update()?;
+        Ok(dir)
+    }
+
+    pub fn open(
+        location: Location,
+        sorter: Sorter,
+        visibility: Arc<Visibility>,
+        sizes: Arc<SizeCache>,
+    ) -> io::Result<Self> {
+        let (archive, inner) = match location {
+            Location::Dir(path) => return Self::new(path, sorter, visibility, sizes),
+            Location::Archive { archive, inner } => (archive, inner),
+        };
+
+        let path = Location::archive_path(&archive, &inner);
+        let mut dir = Self {
+            parent: path.parent().map(Path::to_path_buf),
+            path,
+            sorter,
+            visibility,
+            sizes,
+            archive: Some(archive),
+            ..Self::default()
+        };
+        dir.update()?;
+        Ok(dir)
     }
 
-    // fn should_update(&self) -> bool {
-    //     self.last_update.elapsed() >= Duration::from_secs(1)
-    // }
+    pub fn location(&self) -> Location {
+        let inner = self.archive.as_ref().and_then(|a| a.inner(&self.path));
+        match (&self.archive, inner) {
+            (Some(archive), Some(inner)) => Location::Archive {
+                archive: archive.clone(),
+                inner: inner.to_path_buf(),
+            },
+            _ => Location::Dir(self.path.clone()),
+        }
+    }
+
+    /// Sorts again without reading the directory again.
+    pub fn resort(&mut self) {
+        let sorter = self.sorter;
+        self.contents.sort_by(|a, b| sorter.entries(a, b));
+    }
+
+    /// For directories that are polled rather than watched.
+    pub fn should_update(&self, interval: Duration) -> bool {
+        self.last_update.elapsed() >= interval
+    }
 
     pub fn update(&mut self) -> io::Result<()> {
-        // if !self.should_update() { return Ok(()); }
-        // TODO currently updated on every terminal tick (250ms),
-        // should have the option to update based on configured interval
-            
-        self.contents = fs::read_dir(&self.path)?
-                .map(|res| res.map(|e| e.path()))
-                .collect::<Result<Vec<_>, io::Error>>()?;
-        self.contents.sort_by(|a, b| SORTER.paths(a, b));
+        let sorter = self.sorter;
+        match &self.archive {
+            // nothing on disk to go by, the archive knows what's a directory
+            Some(archive) => {
+                let inner = archive.inner(&self.path).unwrap_or(Path::new(""));
+                self.contents = archive
+                    .children(inner)
+                    .into_iter()
+                    .map(|(path, dir)| FileSystemEntry::member(path, dir))
+                    .collect();
+            }
+            None => {
+                self.contents = fs::read_dir(&self.path)?
+                    .map(|res| res.map(|e| FileSystemEntry::new(e.path())))
+                    .collect::<Result<Vec<_>, io::Error>>()?;
+            }
+        }
+        self.hidden = self
+            .visibility
+            .apply(&self.path, &mut self.contents, self.archive.is_none());
+        for entry in self.contents.iter_mut().filter(|e| e.kind == FileKind::Dir) {
+            entry.dir_size = self.sizes.get(entry);
+        }
+        self.contents.sort_by(|a, b| sorter.entries(a, b));
+        if let Some(filter) = &self.filter {
+            self.contents
+                .retain(|e| search::matches(&e.name().to_string_lossy(), filter));
+        }
 
         self.last_update = Instant::now();
         Ok(())
     }
+
+    pub fn set_filter(&mut self, filter: Option<String>) -> io::Result<()> {
+        self.filter = filter;
+        self.update()
+    }
+
+    /// A directory in here was sized, sorting again if that's the order.
+    pub fn set_dir_size(&mut self, path: &Path, size: u64) {
+        let Some(entry) = self.contents.iter_mut().find(|e| e.path == path) else {
+            return;
+        };
+        entry.dir_size = Some(size);
+        if self.sorter.key == SortKey::Size {
+            self.resort();
+        }
+    }
+
+    pub fn set_visibility(&mut self, visibility: Arc<Visibility>) -> io::Result<()> {
+        self.visibility = visibility;
+        self.update()
+    }
 }
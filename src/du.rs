@@ -0,0 +1,200 @@
+// directory sizes are walked on the blocking pool, one directory after the
+// other so a big listing doesn't start a thread per entry. Each size is sent
+// back as soon as it's known and the listings fill it in from the cache
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{event::Event, file_entry::FileKind, filesystem_entry::FileSystemEntry};
+
+/// A directory's device and inode, it keeps them through renames.
+type Key = (u64, u64);
+
+/// Sizes by inode, only good as long as the directory's mtime didn't move.
+/// Changes further down don't move it, so those are only picked up when
+/// asked to size again with `fresh`.
+#[derive(Debug, Default)]
+pub struct SizeCache {
+    sizes: Mutex<HashMap<Key, (Option<SystemTime>, u64)>>,
+}
+
+impl SizeCache {
+    pub fn get(&self, entry: &FileSystemEntry) -> Option<u64> {
+        let sizes = self.sizes.lock().unwrap();
+        match sizes.get(&(entry.dev, entry.inode)) {
+            Some((modified, size)) if *modified == entry.modified => Some(*size),
+            _ => None,
+        }
+    }
+
+    fn insert(&self, size: &DirSize) {
+        let mut sizes = self.sizes.lock().unwrap();
+        sizes.insert(size.key, (size.modified, size.size));
+    }
+}
+
+#[derive(Debug)]
+pub struct DirSize {
+    pub path: PathBuf,
+    key: Key,
+    modified: Option<SystemTime>,
+    pub size: u64,
+}
+
+/// Directories of one listing being walked.
+#[derive(Debug)]
+struct Batch {
+    dir: PathBuf,
+    pending: HashSet<PathBuf>,
+    total: usize,
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+pub struct DiskUsage {
+    sender: UnboundedSender<Event>,
+    pub cache: Arc<SizeCache>,
+    batch: Option<Batch>,
+}
+
+impl DiskUsage {
+    pub fn new(sender: UnboundedSender<Event>, cache: Arc<SizeCache>) -> Self {
+        Self {
+            sender,
+            cache,
+            batch: None,
+        }
+    }
+
+    /// Sizes every directory in `entries` that isn't known yet, or all of
+    /// them when `fresh`. `dir` is what they're listed in. Whatever was
+    /// running for another listing is stopped.
+    pub fn compute(&mut self, dir: &Path, entries: &[FileSystemEntry], fresh: bool) {
+        let dirs: Vec<_> = entries
+            .iter()
+            .filter(|e| e.kind == FileKind::Dir && (fresh || self.cache.get(e).is_none()))
+            .filter(|e| !self.is_pending(&e.path))
+            .map(|e| (e.path.clone(), (e.dev, e.inode), e.modified))
+            .collect();
+        if self.batch.as_ref().is_some_and(|b| b.dir != dir) {
+            self.cancel();
+        }
+        if dirs.is_empty() {
+            return;
+        }
+
+        let batch = self.batch.get_or_insert_with(|| Batch {
+            dir: dir.to_path_buf(),
+            pending: HashSet::new(),
+            total: 0,
+            cancel: Arc::new(AtomicBool::new(false)),
+        });
+        batch
+            .pending
+            .extend(dirs.iter().map(|(path, _, _)| path.clone()));
+        batch.total += dirs.len();
+
+        let (sender, cancel) = (self.sender.clone(), batch.cancel.clone());
+        tokio::task::spawn_blocking(move || {
+            for (path, key, modified) in dirs {
+                let Some(size) = walk(&path, key.0, &cancel) else {
+                    return;
+                };
+                let size = DirSize {
+                    path,
+                    key,
+                    modified,
+                    size,
+                };
+                if sender.send(Event::DirSize(size)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Stops the walks, sizes already in stay cached.
+    pub fn cancel(&mut self) {
+        if let Some(batch) = self.batch.take() {
+            batch.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.batch.is_some()
+    }
+
+    /// The listing being sized, if any.
+    pub fn dir(&self) -> Option<&Path> {
+        self.batch.as_ref().map(|b| b.dir.as_path())
+    }
+
+    pub fn is_pending(&self, path: &Path) -> bool {
+        self.batch
+            .as_ref()
+            .is_some_and(|b| b.pending.contains(path))
+    }
+
+    /// How many are done out of how many were asked for.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        let batch = self.batch.as_ref()?;
+        Some((batch.total - batch.pending.len(), batch.total))
+    }
+
+    /// Sizes from a cancelled batch are still right, they're kept.
+    pub fn handle_ready(&mut self, size: &DirSize) {
+        self.cache.insert(size);
+        if let Some(batch) = &mut self.batch {
+            batch.pending.remove(&size.path);
+            if batch.pending.is_empty() {
+                self.batch = None;
+            }
+        }
+    }
+}
+
+/// Apparent size of everything under `dir` like `du -sbx`, hard links
+/// counted once and other filesystems left alone. Unreadable directories
+/// count as empty.
+fn walk(dir: &Path, dev: u64, cancel: &AtomicBool) -> Option<u64> {
+    let mut total = fs::symlink_metadata(dir).map_or(0, |m| m.len());
+    let mut seen = HashSet::new();
+    let mut stack = vec![dir.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            // doesn't follow symlinks
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.dev() != dev {
+                continue;
+            }
+            if meta.nlink() > 1 && !meta.is_dir() && !seen.insert(meta.ino()) {
+                continue;
+            }
+            total += meta.len();
+            if meta.is_dir() {
+                stack.push(entry.path());
+            }
+        }
+    }
+    Some(total)
+}
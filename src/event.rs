@@ -1,13 +1,16 @@
-use std::time::Duration;
+use std::{collections::HashSet, io, path::PathBuf, time::Duration};
 
 use crossterm::event::{Event as CrosstermEvent, KeyEvent, MouseEvent};
 use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc;
 
-use crate::app::AppResult;
+use crate::{
+    app::AppResult, archive::Archive, du::DirSize, finder::FinderEvent, git::RepoStatus,
+    grep::GrepEvent, jobs::JobEvent, preview::Preview, usage::UsageEvent,
+};
 
 /// Terminal events.
-#[derive(Clone, Copy, Debug)]
+#[derive(Debug)]
 pub enum Event {
     /// Terminal tick.
     Tick,
@@ -17,6 +20,24 @@ pub enum Event {
     Mouse(MouseEvent),
     /// Terminal resize.
     Resize(u16, u16),
+    /// Background file operation progress.
+    Job(JobEvent),
+    /// Results of a fuzzy finder walk.
+    Finder(FinderEvent),
+    /// Hits of a content search.
+    Grep(GrepEvent),
+    /// A preview made in the background.
+    PreviewReady(Preview),
+    /// Directories whose listings changed, and paths that changed in them.
+    FsChanged(HashSet<PathBuf>),
+    /// Status of the repository the focus dir is in, if it's in one.
+    GitStatus(Option<RepoStatus>),
+    /// A directory walked to the end.
+    DirSize(DirSize),
+    /// Progress of a disk usage scan.
+    Usage(UsageEvent),
+    /// An archive listed before moving into it.
+    ArchiveOpened(PathBuf, io::Result<Archive>),
 }
 
 /// Terminal event handler.
@@ -81,6 +102,11 @@ impl EventHandler {
         }
     }
 
+    /// Returns a sender so background tasks can feed events into the main loop.
+    pub fn sender(&self) -> mpsc::UnboundedSender<Event> {
+        self.sender.clone()
+    }
+
     /// Receive the next event from the handler thread.
     ///
     /// This function will always block the current thread if
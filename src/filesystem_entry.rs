@@ -0,0 +1,129 @@
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::file_entry::FileKind;
+
+/// One line of a listing. Everything the panes and the sorter look at comes
+/// from a single `symlink_metadata` call when the directory is read, rather
+/// than asking the filesystem again on every draw.
+#[derive(Debug, Clone)]
+pub struct FileSystemEntry {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    /// Where a symlink points, as written in the link.
+    pub target: Option<PathBuf>,
+    /// Symlinks to directories are walked into like directories.
+    target_is_dir: bool,
+    pub size: u64,
+    /// Everything under a directory, once it's been walked.
+    pub dir_size: Option<u64>,
+    /// Permission bits and file type, as `st_mode`.
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub nlink: u64,
+    pub dev: u64,
+    pub inode: u64,
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    /// Last status change, `st_ctime`.
+    pub changed: Option<SystemTime>,
+}
+
+impl FileSystemEntry {
+    /// Never fails, something that can't be stat'ed is listed as
+    /// [`FileKind::Unknown`]. Symlinks take a second call to see what they
+    /// point at.
+    pub fn new(path: PathBuf) -> Self {
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            return Self::bare(path, FileKind::Unknown);
+        };
+
+        let kind = FileKind::from(meta.file_type());
+        let (target, target_is_dir) = match kind {
+            FileKind::Symlink => (fs::read_link(&path).ok(), path.is_dir()),
+            _ => (None, false),
+        };
+
+        Self {
+            kind,
+            target,
+            target_is_dir,
+            size: meta.len(),
+            dir_size: None,
+            mode: meta.mode(),
+            uid: meta.uid(),
+            gid: meta.gid(),
+            nlink: meta.nlink(),
+            dev: meta.dev(),
+            inode: meta.ino(),
+            modified: meta.modified().ok(),
+            accessed: meta.accessed().ok(),
+            changed: time(meta.ctime(), meta.ctime_nsec()),
+            path,
+        }
+    }
+
+    /// A member of an archive, nothing on disk to stat.
+    pub fn member(path: PathBuf, dir: bool) -> Self {
+        let kind = if dir { FileKind::Dir } else { FileKind::File };
+        Self::bare(path, kind)
+    }
+
+    fn bare(path: PathBuf, kind: FileKind) -> Self {
+        Self {
+            path,
+            kind,
+            target: None,
+            target_is_dir: false,
+            size: 0,
+            dir_size: None,
+            mode: 0,
+            uid: 0,
+            gid: 0,
+            nlink: 0,
+            dev: 0,
+            inode: 0,
+            modified: None,
+            accessed: None,
+            changed: None,
+        }
+    }
+
+    /// Directories and symlinks to them.
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir || self.target_is_dir
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FileKind::Symlink
+    }
+
+    /// What a directory holds once it's been sized, `None` until then.
+    pub fn total_size(&self) -> Option<u64> {
+        match self.kind {
+            FileKind::Dir => self.dir_size,
+            _ => Some(self.size),
+        }
+    }
+
+    /// Whether there's anything past the name and kind, archive members and
+    /// entries that couldn't be stat'ed only have those.
+    pub fn has_metadata(&self) -> bool {
+        // the type bits are never all zero for something that exists
+        self.mode != 0
+    }
+
+    pub fn name(&self) -> &Path {
+        self.path.file_name().map_or(&self.path, Path::new)
+    }
+}
+
+fn time(secs: i64, nanos: i64) -> Option<SystemTime> {
+    let since_epoch = Duration::new(secs.try_into().ok()?, nanos.try_into().ok()?);
+    SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+}
@@ -0,0 +1,357 @@
+// the tree under a directory is walked on the blocking pool and streamed back
+// in batches. Each batch is scored as it comes in and merged into the ranking,
+// a changed query ranks everything again on the blocking pool as well
+
+use std::{
+    cmp::Ordering as CmpOrdering,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use ignore::WalkBuilder;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{event::Event, input::Input};
+
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+const BATCH_SIZE: usize = 20_000;
+/// Past this the walk stops, nobody scrolls through more than that anyway.
+const MAX_ITEMS: usize = 200_000;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub enum FinderEvent {
+    Found {
+        id: usize,
+        paths: Vec<(PathBuf, bool)>,
+    },
+    Done {
+        id: usize,
+    },
+    /// The first `scored` items ranked again, for the query as it was when
+    /// `generation` started.
+    Ranked {
+        id: usize,
+        generation: usize,
+        ranked: Vec<Match>,
+        scored: usize,
+    },
+}
+
+#[derive(Debug)]
+pub struct Item {
+    pub path: PathBuf,
+    /// Path relative to the root, what gets matched and shown. Dirs end in '/'.
+    pub text: String,
+}
+
+/// Everything found, in the batches it came in. Cloning it only clones the
+/// handles, so ranking on the blocking pool doesn't hold up the walk.
+#[derive(Debug, Clone, Default)]
+pub struct Items {
+    batches: Vec<Arc<[Item]>>,
+    /// Index of the first item of each batch.
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl Items {
+    pub fn get(&self, idx: usize) -> &Item {
+        let batch = self.starts.partition_point(|&start| start <= idx) - 1;
+        &self.batches[batch][idx - self.starts[batch]]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, batch: Arc<[Item]>) {
+        self.starts.push(self.len);
+        self.len += batch.len();
+        self.batches.push(batch);
+    }
+}
+
+#[derive(Debug)]
+pub struct Match {
+    pub item: usize,
+    score: i64,
+    /// Of the item's text, ties go to shorter paths.
+    len: usize,
+}
+
+#[derive(Debug)]
+pub struct Finder {
+    id: usize,
+    pub root: PathBuf,
+    pub query: Input,
+    pub items: Items,
+    /// Best first.
+    pub ranked: Vec<Match>,
+    pub selected: usize,
+    pub done: bool,
+    /// Items before this one are in `ranked`, the rest came in while it was
+    /// being ranked again.
+    scored: usize,
+    /// Bumped for every new ranking, results for older queries are dropped.
+    generation: usize,
+    reranking: bool,
+    sender: UnboundedSender<Event>,
+    cancel: Arc<AtomicBool>,
+    rerank_cancel: Arc<AtomicBool>,
+}
+
+impl Finder {
+    /// Starts walking `root` right away, results arrive as [`FinderEvent`]s.
+    pub fn new(root: PathBuf, sender: UnboundedSender<Event>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let walk_root = root.clone();
+        let (walk_sender, walk_cancel) = (sender.clone(), cancel.clone());
+        tokio::task::spawn_blocking(move || walk(id, walk_root, walk_sender, walk_cancel));
+
+        Self {
+            id,
+            root,
+            query: Input::new(),
+            items: Items::default(),
+            ranked: Vec::new(),
+            selected: 0,
+            done: false,
+            scored: 0,
+            generation: 0,
+            reranking: false,
+            sender,
+            cancel,
+            rerank_cancel: Arc::default(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: FinderEvent) {
+        match event {
+            FinderEvent::Found { id, paths } if id == self.id => {
+                let batch = paths.into_iter().map(|(rel, dir)| {
+                    let mut text = rel.to_string_lossy().into_owned();
+                    if dir {
+                        text.push('/');
+                    }
+                    Item {
+                        path: self.root.join(rel),
+                        text,
+                    }
+                });
+                self.items.push(batch.collect());
+                // otherwise it's picked up once the new ranking is in
+                if !self.reranking {
+                    self.rank_new();
+                }
+            }
+            FinderEvent::Done { id } if id == self.id => self.done = true,
+            FinderEvent::Ranked {
+                id,
+                generation,
+                ranked,
+                scored,
+            } if id == self.id && generation == self.generation => {
+                self.ranked = ranked;
+                self.scored = scored;
+                self.reranking = false;
+                self.selected = 0;
+                self.rank_new();
+            }
+            // a finder that was closed in the meantime, or an older query
+            _ => {}
+        }
+    }
+
+    pub fn insert_char(&mut self, ch: char) {
+        let appended = self.query.char_idx == self.query.content.chars().count();
+        self.query.insert_char(ch);
+
+        // a longer query can only match fewer items, no need to look at the rest
+        self.rerank(appended && self.query.content.chars().count() > 1);
+    }
+
+    pub fn delete_char(&mut self) {
+        self.query.delete_char();
+        self.rerank(false);
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.ranked.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Char positions in the item's text that matched the query, only worked
+    /// out for rows that are actually drawn.
+    pub fn indices(&self, m: &Match) -> Vec<usize> {
+        SkimMatcherV2::default()
+            .fuzzy_indices(&self.items.get(m.item).text, &self.query.content)
+            .map(|(_, indices)| indices)
+            .unwrap_or_default()
+    }
+
+    /// Still walking, or ranking for a new query.
+    pub fn is_busy(&self) -> bool {
+        !self.done || self.reranking
+    }
+
+    pub fn selected_path(&self) -> Option<&PathBuf> {
+        let m = self.ranked.get(self.selected)?;
+        Some(&self.items.get(m.item).path)
+    }
+
+    /// Ranks everything found so far for the new query on the blocking pool,
+    /// or only what's ranked already when `narrow`. The old ranking stays up
+    /// until then.
+    fn rerank(&mut self, narrow: bool) {
+        self.rerank_cancel.store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.rerank_cancel = cancel.clone();
+
+        // what's ranked may be for an older query, one that isn't a prefix
+        let candidates = (narrow && !self.reranking)
+            .then(|| self.ranked.iter().map(|m| m.item).collect::<Vec<_>>());
+        self.generation += 1;
+        self.reranking = true;
+
+        let (id, generation, scored) = (self.id, self.generation, self.items.len());
+        let (items, query) = (self.items.clone(), self.query.content.clone());
+        let sender = self.sender.clone();
+        tokio::task::spawn_blocking(move || {
+            let matcher = SkimMatcherV2::default();
+            let candidates = candidates.unwrap_or_else(|| (0..items.len()).collect());
+            let mut ranked = Vec::new();
+            for idx in candidates {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                ranked.extend(score(&matcher, idx, items.get(idx), &query));
+            }
+            ranked.sort_by(rank);
+
+            let event = FinderEvent::Ranked {
+                id,
+                generation,
+                ranked,
+                scored,
+            };
+            let _ = sender.send(Event::Finder(event));
+        });
+    }
+
+    /// Scores the items that came in since the last ranking and merges them
+    /// into it.
+    fn rank_new(&mut self) {
+        let matcher = SkimMatcherV2::default();
+        let query = &self.query.content;
+        let mut new: Vec<_> = (self.scored..self.items.len())
+            .filter_map(|idx| score(&matcher, idx, self.items.get(idx), query))
+            .collect();
+        new.sort_by(rank);
+
+        let mut merged = Vec::with_capacity(self.ranked.len() + new.len());
+        let mut old = std::mem::take(&mut self.ranked).into_iter().peekable();
+        let mut new = new.into_iter().peekable();
+        while let (Some(a), Some(b)) = (old.peek(), new.peek()) {
+            let next = match rank(a, b) {
+                CmpOrdering::Greater => new.next(),
+                _ => old.next(),
+            };
+            merged.extend(next);
+        }
+        merged.extend(old.chain(new));
+
+        self.ranked = merged;
+        self.scored = self.items.len();
+        self.selected = self.selected.min(self.ranked.len().saturating_sub(1));
+    }
+}
+
+impl Drop for Finder {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.rerank_cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// `None` if it doesn't match. Without a query everything does, in the order
+/// it was found.
+fn score(matcher: &SkimMatcherV2, idx: usize, item: &Item, query: &str) -> Option<Match> {
+    if query.is_empty() {
+        return Some(Match {
+            item: idx,
+            score: 0,
+            len: 0,
+        });
+    }
+    let score = matcher.fuzzy_match(&item.text, query)?;
+    Some(Match {
+        item: idx,
+        score,
+        len: item.text.len(),
+    })
+}
+
+/// Best first, ties go to shorter paths, then to whatever was found first.
+fn rank(a: &Match, b: &Match) -> CmpOrdering {
+    b.score
+        .cmp(&a.score)
+        .then_with(|| a.len.cmp(&b.len))
+        .then_with(|| a.item.cmp(&b.item))
+}
+
+fn walk(id: usize, root: PathBuf, sender: UnboundedSender<Event>, cancel: Arc<AtomicBool>) {
+    let mut batch = Vec::new();
+    let mut total = 0;
+    let mut last_report = Instant::now();
+
+    for entry in WalkBuilder::new(&root).build() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(entry) = entry else {
+            continue;
+        };
+        let Ok(rel) = entry.path().strip_prefix(&root) else {
+            continue;
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+
+        let dir = entry.file_type().is_some_and(|t| t.is_dir());
+        batch.push((rel.to_path_buf(), dir));
+        total += 1;
+
+        if batch.len() >= BATCH_SIZE || last_report.elapsed() >= BATCH_INTERVAL {
+            let paths = std::mem::take(&mut batch);
+            let _ = sender.send(Event::Finder(FinderEvent::Found { id, paths }));
+            last_report = Instant::now();
+        }
+        if total >= MAX_ITEMS {
+            break;
+        }
+    }
+
+    if !batch.is_empty() {
+        let _ = sender.send(Event::Finder(FinderEvent::Found { id, paths: batch }));
+    }
+    let _ = sender.send(Event::Finder(FinderEvent::Done { id }));
+}
@@ -0,0 +1,96 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Copies `from` to `to`, descending into directories.
+///
+/// Symlinks are recreated rather than followed.
+pub fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+
+    if meta.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, meta.permissions())
+    } else if meta.is_symlink() {
+        copy_symlink(from, to)
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Removes a file, symlink or whole directory tree.
+pub fn remove_path(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Renames `from` to `to`, falling back to copy + remove when they live on
+/// different filesystems.
+pub fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_recursive(from, to)?;
+            remove_path(from)
+        }
+        res => res,
+    }
+}
+
+/// True if anything (including a dangling symlink) lives at `path`.
+pub fn occupied(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// Returns `dir/name` if it is free, otherwise the first free
+/// `dir/stem (n).ext`.
+pub fn unique_path(dir: &Path, name: &std::ffi::OsStr) -> PathBuf {
+    let candidate = dir.join(name);
+    if !occupied(&candidate) {
+        return candidate;
+    }
+
+    let as_path = Path::new(name);
+    let stem = as_path.file_stem().unwrap_or(name).to_os_string();
+    let ext = as_path.extension().map(|e| e.to_os_string());
+
+    (1..)
+        .map(|n| {
+            let mut file_name = stem.clone();
+            file_name.push(format!(" ({})", n));
+            if let Some(ext) = &ext {
+                file_name.push(".");
+                file_name.push(ext);
+            }
+            dir.join(file_name)
+        })
+        .find(|p| !occupied(p))
+        .expect("ran out of numbers")
+}
+
+/// Lossy display name of a path, used in messages.
+pub fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(OsString::from)
+        .unwrap_or_else(|| path.as_os_str().to_os_string())
+        .to_string_lossy()
+        .into_owned()
+}
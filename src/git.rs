@@ -0,0 +1,222 @@
+// git status is read on the blocking pool, a big work tree can take a while.
+// The focus dir decides which repository is looked at, and the whole work
+// tree is read at once so moving around inside it costs nothing
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use git2::{BranchType, Repository, Status, StatusOptions};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::event::Event;
+
+/// Ordered by how much it matters, a directory shows the highest of what's
+/// inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    Ignored,
+    Untracked,
+    Staged,
+    Modified,
+    Conflicted,
+}
+
+impl GitStatus {
+    /// Changes in the work tree win over staged ones for the same file.
+    fn from_status(status: Status) -> Option<Self> {
+        let modified =
+            Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_RENAMED | Status::WT_TYPECHANGE;
+        let staged = Status::INDEX_NEW
+            | Status::INDEX_MODIFIED
+            | Status::INDEX_DELETED
+            | Status::INDEX_RENAMED
+            | Status::INDEX_TYPECHANGE;
+
+        if status.is_conflicted() {
+            Some(GitStatus::Conflicted)
+        } else if status.intersects(modified) {
+            Some(GitStatus::Modified)
+        } else if status.intersects(staged) {
+            Some(GitStatus::Staged)
+        } else if status.is_wt_new() {
+            Some(GitStatus::Untracked)
+        } else if status.is_ignored() {
+            Some(GitStatus::Ignored)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RepoStatus {
+    pub workdir: PathBuf,
+    /// Watched for changes to the index and HEAD.
+    pub gitdir: PathBuf,
+    /// The short commit id when detached.
+    pub branch: String,
+    pub ahead: usize,
+    pub behind: usize,
+    /// As git reports them, untracked and ignored directories aren't
+    /// looked into.
+    entries: HashMap<PathBuf, GitStatus>,
+    /// Everything above a changed entry, up to and including the work tree.
+    dirs: HashMap<PathBuf, GitStatus>,
+}
+
+impl RepoStatus {
+    fn read(dir: &Path) -> Option<Self> {
+        let repo = Repository::discover(dir).ok()?;
+        let workdir = repo.workdir()?.to_path_buf();
+
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .include_ignored(true)
+            .recurse_untracked_dirs(false)
+            .recurse_ignored_dirs(false);
+        let statuses = repo.statuses(Some(&mut options)).ok()?;
+
+        let mut status = Self {
+            gitdir: repo.path().to_path_buf(),
+            branch: branch(&repo),
+            ..Self::default()
+        };
+        (status.ahead, status.behind) = ahead_behind(&repo).unwrap_or_default();
+
+        for entry in statuses.iter() {
+            let (Some(path), Some(kind)) = (entry.path(), GitStatus::from_status(entry.status()))
+            else {
+                continue;
+            };
+            let path = workdir.join(path.trim_end_matches('/'));
+            if kind != GitStatus::Ignored {
+                for dir in path.ancestors().skip(1) {
+                    let aggregate = status.dirs.entry(dir.to_path_buf()).or_insert(kind);
+                    *aggregate = kind.max(*aggregate);
+                    if dir == workdir {
+                        break;
+                    }
+                }
+            }
+            status.entries.insert(path, kind);
+        }
+        status.workdir = workdir;
+        Some(status)
+    }
+
+    /// Anything inside an untracked or ignored directory is too.
+    pub fn get(&self, path: &Path) -> Option<GitStatus> {
+        if let Some(status) = self.entries.get(path).or_else(|| self.dirs.get(path)) {
+            return Some(*status);
+        }
+        path.ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.workdir))
+            .filter_map(|dir| self.entries.get(dir))
+            .find(|s| matches!(s, GitStatus::Untracked | GitStatus::Ignored))
+            .copied()
+    }
+}
+
+fn branch(repo: &Repository) -> String {
+    match repo.head() {
+        Ok(head) if head.is_branch() => head.shorthand().unwrap_or("HEAD").to_string(),
+        Ok(head) => head
+            .target()
+            .map_or("HEAD".to_string(), |id| id.to_string()[..7].to_string()),
+        // nothing committed yet, HEAD points at a branch that doesn't exist
+        Err(_) => repo
+            .find_reference("HEAD")
+            .ok()
+            .and_then(|r| r.symbolic_target().map(str::to_string))
+            .map_or("HEAD".to_string(), |t| {
+                t.trim_start_matches("refs/heads/").to_string()
+            }),
+    }
+}
+
+fn ahead_behind(repo: &Repository) -> Option<(usize, usize)> {
+    let head = repo.head().ok()?;
+    let local = repo
+        .find_branch(head.shorthand()?, BranchType::Local)
+        .ok()?;
+    let upstream = local.upstream().ok()?;
+    repo.graph_ahead_behind(local.get().target()?, upstream.get().target()?)
+        .ok()
+}
+
+#[derive(Debug)]
+pub struct Git {
+    sender: UnboundedSender<Event>,
+    pub status: Option<RepoStatus>,
+    /// The directory last asked about, its repository may not exist.
+    asked: Option<PathBuf>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Git {
+    pub fn new(sender: UnboundedSender<Event>) -> Self {
+        Self {
+            sender,
+            status: None,
+            asked: None,
+            cancel: Arc::default(),
+        }
+    }
+
+    /// Reads the repository `dir` is in, unless that's already known.
+    pub fn follow(&mut self, dir: &Path) {
+        let known = self.asked.as_deref() == Some(dir)
+            || self
+                .status
+                .as_ref()
+                .is_some_and(|s| dir.starts_with(&s.workdir));
+        if !known {
+            self.request(dir.to_path_buf());
+        }
+    }
+
+    /// Reads the status again, something in the work tree or the git
+    /// directory changed.
+    pub fn refresh(&mut self) {
+        if let Some(dir) = self.asked.clone() {
+            self.request(dir);
+        }
+    }
+
+    /// Whether a change to `path` could change the status.
+    pub fn affected_by(&self, path: &Path) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| path.starts_with(&s.workdir) || path.starts_with(&s.gitdir))
+    }
+
+    fn request(&mut self, dir: PathBuf) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.asked = Some(dir.clone());
+
+        let (sender, cancel) = (self.sender.clone(), self.cancel.clone());
+        tokio::task::spawn_blocking(move || {
+            let status = RepoStatus::read(&dir);
+            if !cancel.load(Ordering::Relaxed) {
+                let _ = sender.send(Event::GitStatus(status));
+            }
+        });
+    }
+
+    pub fn handle_ready(&mut self, status: Option<RepoStatus>) {
+        self.status = status;
+    }
+
+    pub fn get(&self, path: &Path) -> Option<GitStatus> {
+        self.status.as_ref()?.get(path)
+    }
+}
@@ -0,0 +1,243 @@
+// searches file contents on the blocking pool, hits are streamed back in
+// batches like the finder's
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::event::Event;
+
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_HITS: usize = 10_000;
+/// Longer lines are cut down to this many chars around the match.
+const SNIPPET_LEN: usize = 200;
+/// Longer lines aren't searched, minified files and the like.
+const LINE_LIMIT: u64 = 1 << 20;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub enum GrepEvent {
+    Found { id: usize, hits: Vec<GrepHit> },
+    Done { id: usize, files: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct GrepHit {
+    pub path: PathBuf,
+    /// 1-based, like editors count them.
+    pub line: usize,
+    pub snippet: String,
+    /// Byte range of the match in `snippet`.
+    pub matched: Range<usize>,
+}
+
+#[derive(Debug)]
+pub struct Grep {
+    id: usize,
+    pub root: PathBuf,
+    pub pattern: String,
+    pub hits: Vec<GrepHit>,
+    pub selected: usize,
+    /// Number of files searched, once the walk is over.
+    pub done: Option<usize>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Grep {
+    /// Starts searching right away. Lowercase patterns ignore case, like the
+    /// `/` search.
+    pub fn new(
+        root: PathBuf,
+        pattern: String,
+        sender: UnboundedSender<Event>,
+    ) -> Result<Self, String> {
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!pattern.chars().any(char::is_uppercase))
+            .build()
+            .map_err(|e| e.to_string().lines().last().unwrap_or_default().to_string())?;
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let walk_root = root.clone();
+        let walk_cancel = cancel.clone();
+        tokio::task::spawn_blocking(move || search(id, walk_root, regex, sender, walk_cancel));
+
+        Ok(Self {
+            id,
+            root,
+            pattern,
+            hits: Vec::new(),
+            selected: 0,
+            done: None,
+            cancel,
+        })
+    }
+
+    pub fn handle_event(&mut self, event: GrepEvent) {
+        match event {
+            GrepEvent::Found { id, hits } if id == self.id => self.hits.extend(hits),
+            GrepEvent::Done { id, files } if id == self.id => self.done = Some(files),
+            _ => {}
+        }
+    }
+
+    pub fn selected_hit(&self) -> Option<&GrepHit> {
+        self.hits.get(self.selected)
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.hits.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn go_top(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn go_bottom(&mut self) {
+        self.selected = self.hits.len().saturating_sub(1);
+    }
+}
+
+impl Drop for Grep {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn search(
+    id: usize,
+    root: PathBuf,
+    regex: Regex,
+    sender: UnboundedSender<Event>,
+    cancel: Arc<AtomicBool>,
+) {
+    let mut batch = Vec::new();
+    let mut files = 0;
+    let mut total = 0;
+    let mut last_report = Instant::now();
+
+    // .gitignore is honoured even outside of a git repo
+    let walker = WalkBuilder::new(&root).require_git(false).build();
+    'walk: for entry in walker {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        files += 1;
+
+        let Ok(hits) = search_file(entry.path(), &regex, &cancel) else {
+            continue;
+        };
+        for hit in hits {
+            batch.push(hit);
+            total += 1;
+            if total >= MAX_HITS {
+                break 'walk;
+            }
+        }
+
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        if !batch.is_empty() && last_report.elapsed() >= BATCH_INTERVAL {
+            let hits = std::mem::take(&mut batch);
+            let _ = sender.send(Event::Grep(GrepEvent::Found { id, hits }));
+            last_report = Instant::now();
+        }
+    }
+
+    if !batch.is_empty() {
+        let _ = sender.send(Event::Grep(GrepEvent::Found { id, hits: batch }));
+    }
+    let _ = sender.send(Event::Grep(GrepEvent::Done { id, files }));
+}
+
+fn search_file(path: &Path, regex: &Regex, cancel: &AtomicBool) -> std::io::Result<Vec<GrepHit>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    // same heuristic as git and grep: a NUL early on means binary
+    let head = reader.fill_buf()?;
+    if head[..head.len().min(8192)].contains(&0) {
+        return Ok(Vec::new());
+    }
+
+    let mut hits = Vec::new();
+    let mut buf = Vec::new();
+    let mut line = 0;
+    loop {
+        buf.clear();
+        // lines are read as bytes so one bad char doesn't end the file
+        let read = reader
+            .by_ref()
+            .take(LINE_LIMIT)
+            .read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        line += 1;
+        if line % 4096 == 0 && cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        // the rest of a line too long to search, it still counts as one
+        if read as u64 == LINE_LIMIT && !buf.ends_with(b"\n") {
+            reader.skip_until(b'\n')?;
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&buf);
+        let text = text.trim_end_matches(['\n', '\r']);
+        if let Some(m) = regex.find(text) {
+            let (snippet, matched) = snippet(text, m.range());
+            hits.push(GrepHit {
+                path: path.to_path_buf(),
+                line,
+                snippet,
+                matched,
+            });
+        }
+    }
+
+    Ok(hits)
+}
+
+/// Cuts long lines down around the match and drops leading indentation.
+fn snippet(text: &str, matched: Range<usize>) -> (String, Range<usize>) {
+    let indent = text.len() - text.trim_start().len();
+    let mut start = indent.min(matched.start);
+    if matched.start - start > SNIPPET_LEN / 2 {
+        start = matched.start - SNIPPET_LEN / 2;
+    }
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+
+    let mut end = text.len().min(start + SNIPPET_LEN).max(matched.end);
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let snippet = text[start..end].replace('\t', " ");
+    (snippet, matched.start - start..matched.end - start)
+}
@@ -1,21 +1,45 @@
-use crate::app::{App, AppResult};
+use crate::{
+    app::{App, AppResult},
+    command::Command,
+    config::Request,
+    keymap::{Binding, KeyChord, Lookup},
+    register::RegisterKind,
+    sorter,
+};
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 pub struct KeyHandler {
     mode: Mode,
     action: Action,
+    /// Keys typed so far of a multi-key binding like `gg`.
+    pending: Vec<KeyChord>,
 }
 
 enum Action {
     None,
+    Trash,
     Delete,
+    Purge,
     Rename,
+    Command,
+    Search,
+    Filter,
+    Grep,
+    Extract,
+    Quit,
     Move,
 }
 
 enum Mode {
     Normal,
     Action(Action),
+    TrashView,
+    Buffer,
+    Jobs,
+    Finder,
+    Grep,
+    SortMenu,
+    Usage,
     // Input,
 }
 
@@ -24,6 +48,7 @@ impl Default for KeyHandler {
         Self {
             mode: Mode::Normal,
             action: Action::None,
+            pending: Vec::new(),
         }
     }
 }
@@ -39,6 +64,13 @@ impl KeyHandler {
         self.mode = match mode {
             Mode::Action(action) => self.handle_action(app, action, key_event),
             Mode::Normal => self.handle_normal_mode(app, key_event),
+            Mode::TrashView => self.handle_trash_view(app, key_event),
+            Mode::Buffer => self.handle_buffer(app, key_event),
+            Mode::Jobs => self.handle_jobs(app, key_event),
+            Mode::Finder => self.handle_finder(app, key_event),
+            Mode::Grep => self.handle_grep(app, key_event),
+            Mode::SortMenu => self.handle_sort_menu(app, key_event),
+            Mode::Usage => self.handle_usage(app, key_event),
             // Mode::Input => self.handle_input_mode(app, key_event),
         };
 
@@ -47,22 +79,49 @@ impl KeyHandler {
 
     fn handle_action(&mut self, app: &mut App, action: Action, key_event: KeyEvent) -> Mode {
         let mode = match action {
-            Action::Delete => match key_event.code {
+            Action::Trash => match key_event.code {
                 KeyCode::Char('y') | KeyCode::Char('Y') => {
-                    app.delete_selection_or_cursor();
                     app.clear_msg();
-                    Mode::Normal
+                    app.trash_selection_or_cursor();
+                    return removal_done(app);
+                }
+                _ => removal_done(app),
+            },
+            Action::Purge => match key_event.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    app.purge_trash_cursor();
+                    return Mode::TrashView;
+                }
+                _ => Mode::TrashView,
+            },
+            Action::Quit => match key_event.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    app.cancel_jobs_and_quit();
+                    return Mode::Normal;
                 }
                 _ => Mode::Normal,
             },
-            Action::Rename => {
-                self.handle_input(app, key_event)
-                // Mode::Action(Action::Rename)
+            Action::Delete => match key_event.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    app.clear_msg();
+                    app.delete_selection_or_cursor();
+                    return removal_done(app);
+                }
+                _ => removal_done(app),
+            },
+            Action::Rename
+            | Action::Command
+            | Action::Search
+            | Action::Filter
+            | Action::Grep
+            | Action::Extract => {
+                // input decides on its own what to show once it's done
+                return self.handle_input(app, action, key_event);
             }
             _ => Mode::Normal,
         };
 
-        if matches!(mode, Mode::Normal) {
+        if !matches!(mode, Mode::Action(_)) {
             app.clear_msg();
         }
 
@@ -70,62 +129,421 @@ impl KeyHandler {
     }
 
     fn handle_normal_mode(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        app.clear_msg();
+
+        let chord = KeyChord::from_event(&key_event);
+        self.pending.push(chord);
+        let mut lookup = app.config.lookup(&self.pending);
+        // a sequence that went nowhere, the last key may still mean something
+        // on its own
+        if matches!(lookup, Lookup::NotFound) && self.pending.len() > 1 {
+            self.pending = vec![chord];
+            lookup = app.config.lookup(&self.pending);
+        }
+        let binding = match lookup {
+            Lookup::Found(binding) => binding,
+            Lookup::Pending => return Mode::Normal,
+            Lookup::NotFound => {
+                self.pending.clear();
+                return Mode::Normal;
+            }
+        };
+        self.pending.clear();
+
+        match binding {
+            Binding::Command(command) => self.run_command(app, command),
+            Binding::Lua(f) => {
+                let requests = app.config.call(f, app.snapshot(), None);
+                self.run_requests(app, requests, Mode::Normal)
+            }
+        }
+    }
+
+    fn run_command(&mut self, app: &mut App, command: Command) -> Mode {
         let mut mode = Mode::Normal;
 
-        match key_event.code {
-            KeyCode::Esc | KeyCode::Char('q') => {
-                app.quit();
+        match command {
+            // members of an archive can only be looked at or extracted
+            Command::Trash
+            | Command::Delete
+            | Command::Rename
+            | Command::EditBuffer
+            | Command::Yank
+            | Command::Cut
+            | Command::Paste
+                if app.in_archive() =>
+            {
+                app.message = Some("Archives are read-only, extract with E".to_string());
             }
-            KeyCode::Char('c') | KeyCode::Char('C') => {
-                if key_event.modifiers == KeyModifiers::CONTROL {
+            Command::Quit => {
+                if app.jobs.is_empty() {
                     app.quit();
+                } else {
+                    mode = Mode::Action(Action::Quit);
+                    app.show_quit_msg();
                 }
             }
-            KeyCode::Right | KeyCode::Char('l') => {
-                app.move_into();
+            Command::MoveInto => app.move_into(),
+            Command::MoveBack => app.move_back(),
+            Command::MoveUp => app.move_up(),
+            Command::MoveDown => app.move_down(),
+            Command::GoTop => app.go_top(),
+            Command::GoBottom => app.go_bottom(),
+            Command::Search => {
+                mode = Mode::Action(Action::Search);
+                app.show_search_prompt();
             }
-            KeyCode::Left | KeyCode::Char('h') => {
-                app.move_back();
+            Command::SearchNext => app.search_next(),
+            Command::SearchPrev => app.search_prev(),
+            Command::Filter => {
+                mode = Mode::Action(Action::Filter);
+                app.show_filter_prompt();
             }
-            KeyCode::Up | KeyCode::Char('k') => {
-                app.move_up();
+            Command::ClearSearch => app.clear_search(),
+            Command::Grep => {
+                mode = Mode::Action(Action::Grep);
+                app.show_grep_prompt();
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                app.move_down();
+            Command::Find => {
+                mode = Mode::Finder;
+                app.open_finder();
             }
-            KeyCode::Char(' ') => {
+            Command::ToggleSelection => {
                 app.toggle_selection_on_cursor();
                 app.move_down();
             }
-            KeyCode::Char('d') => {
+            Command::Trash => {
+                mode = Mode::Action(Action::Trash);
+                app.show_trash_msg();
+            }
+            Command::Delete => {
                 mode = Mode::Action(Action::Delete);
                 app.show_deletion_msg();
             }
-            KeyCode::Char('r') => {
+            Command::EditBuffer => {
+                mode = Mode::Buffer;
+                app.open_buffer();
+            }
+            Command::DiskUsage => {
+                if app.open_usage() {
+                    mode = Mode::Usage;
+                }
+            }
+            Command::Jobs => {
+                mode = Mode::Jobs;
+                app.open_jobs_view();
+            }
+            Command::TrashView => {
+                if app.open_trash_view() {
+                    mode = Mode::TrashView;
+                }
+            }
+            Command::Yank => app.yank(RegisterKind::Copy),
+            Command::Cut => app.yank(RegisterKind::Cut),
+            Command::Paste => app.paste(),
+            Command::Extract => {
+                if app.show_extract_prompt() {
+                    mode = Mode::Action(Action::Extract);
+                }
+            }
+            Command::Undo => app.undo(),
+            Command::Redo => app.redo(),
+            Command::Rename => {
                 mode = Mode::Action(Action::Rename);
                 app.show_rename_msg();
             }
+            Command::NextPage => app.turn_page(true),
+            Command::PrevPage => app.turn_page(false),
+            Command::SortMenu => {
+                mode = Mode::SortMenu;
+                app.open_sort_menu();
+            }
+            Command::SizeDirs => app.size_dirs(),
+            Command::ToggleDetails => app.toggle_details(),
+            Command::ToggleHidden => app.toggle_hidden(),
+            Command::TogglePreview => app.toggle_preview(),
+            Command::ToggleWrap => app.toggle_wrap(),
+            Command::CommandPrompt => {
+                mode = Mode::Action(Action::Command);
+                app.show_command_prompt();
+            }
+        }
+
+        mode
+    }
+
+    /// Applies what a Lua function asked for, in order.
+    fn run_requests(&mut self, app: &mut App, requests: Vec<Request>, mut mode: Mode) -> Mode {
+        for request in requests {
+            match request {
+                Request::Command(command) => mode = self.run_command(app, command),
+                Request::Cd(path) => app.change_dir(path),
+                Request::Message(text) => app.message = Some(text),
+            }
+        }
+        app.sync_options();
+
+        mode
+    }
+
+    /// Requests left over from loading the config, e.g. a `fear.cd` at the
+    /// top level of init.lua.
+    pub fn run_startup_requests(&mut self, app: &mut App) {
+        let requests = app.config.take_requests();
+        let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+        self.mode = self.run_requests(app, requests, mode);
+    }
+
+    /// Runs a line typed at the `:` prompt, user commands shadow built-in ones.
+    fn run_prompt(&mut self, app: &mut App, line: &str) -> Mode {
+        let line = line.trim();
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        if name.is_empty() {
+            return Mode::Normal;
+        }
+
+        if let Some(f) = app.config.command(name) {
+            let requests = app
+                .config
+                .call(f, app.snapshot(), Some(args.trim().to_string()));
+            return self.run_requests(app, requests, Mode::Normal);
+        }
+
+        match Command::from_name(name) {
+            Some(command) => self.run_command(app, command),
+            None => {
+                app.message = Some(format!("Unknown command: {}", name));
+                Mode::Normal
+            }
+        }
+    }
+
+    /// One key picks an order, or flips the current one, and closes the menu.
+    fn handle_sort_menu(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        app.close_sort_menu();
+        let KeyCode::Char(c) = key_event.code else {
+            return Mode::Normal;
+        };
+
+        let mut sorter = app.focus_dir.sorter;
+        if c == 'r' {
+            sorter.reverse = !sorter.reverse;
+        } else if let Some((key, _, _)) = sorter::KEYS.iter().find(|(_, _, k)| *k == c) {
+            sorter.key = *key;
+        } else {
+            return Mode::Normal;
+        }
+        app.set_sort(sorter);
+        Mode::Normal
+    }
+
+    fn handle_trash_view(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        let mut mode = Mode::TrashView;
+        app.clear_msg();
+
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('T') => {
+                app.close_trash_view();
+                mode = Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => app.trash_move_up(),
+            KeyCode::Down | KeyCode::Char('j') => app.trash_move_down(),
+            KeyCode::Enter | KeyCode::Char('r') => app.restore_trash_cursor(),
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                mode = Mode::Action(Action::Purge);
+                app.show_purge_msg();
+            }
             _ => {}
         }
 
         mode
     }
 
-    fn handle_input(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
-        let mut mode = Mode::Action(Action::Rename);
+    fn handle_usage(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        let mut mode = Mode::Usage;
+        app.clear_msg();
+
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('U') => {
+                app.close_usage();
+                mode = Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => app.usage_move_up(),
+            KeyCode::Down | KeyCode::Char('j') => app.usage_move_down(),
+            KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => app.usage_enter(),
+            KeyCode::Left | KeyCode::Char('h') => app.usage_leave(),
+            // nothing to remove while it's still scanning
+            KeyCode::Char('d') if !app.selection_or_cursor().is_empty() => {
+                mode = Mode::Action(Action::Trash);
+                app.show_trash_msg();
+            }
+            KeyCode::Char('D') if !app.selection_or_cursor().is_empty() => {
+                mode = Mode::Action(Action::Delete);
+                app.show_deletion_msg();
+            }
+            _ => {}
+        }
+
+        mode
+    }
+
+    fn handle_jobs(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        let mut mode = Mode::Jobs;
+        app.clear_msg();
+
+        match key_event.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('J') => {
+                app.close_jobs_view();
+                mode = Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => app.jobs_move_up(),
+            KeyCode::Down | KeyCode::Char('j') => app.jobs_move_down(),
+            KeyCode::Char('c') | KeyCode::Char('x') | KeyCode::Char('d') => {
+                app.cancel_selected_job()
+            }
+            _ => {}
+        }
+
+        mode
+    }
+
+    fn handle_finder(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        app.clear_msg();
+        let Some(finder) = &mut app.finder else {
+            return Mode::Normal;
+        };
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key_event.code {
+            KeyCode::Esc => {
+                app.close_finder();
+                return Mode::Normal;
+            }
+            KeyCode::Enter => {
+                app.confirm_finder();
+                return Mode::Normal;
+            }
+            KeyCode::Up => finder.move_up(),
+            KeyCode::Down => finder.move_down(),
+            KeyCode::Char('p') | KeyCode::Char('k') if ctrl => finder.move_up(),
+            KeyCode::Char('n') | KeyCode::Char('j') if ctrl => finder.move_down(),
+            KeyCode::Left => finder.query.move_cursor(false),
+            KeyCode::Right => finder.query.move_cursor(true),
+            KeyCode::Backspace => finder.delete_char(),
+            KeyCode::Char(c) if !ctrl => finder.insert_char(c),
+            _ => {}
+        }
+
+        Mode::Finder
+    }
+
+    fn handle_grep(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        app.clear_msg();
+        let Some(grep) = &mut app.grep else {
+            return Mode::Normal;
+        };
+
         match key_event.code {
-            KeyCode::Esc => mode = Mode::Normal,
+            KeyCode::Esc | KeyCode::Char('q') => {
+                app.close_grep();
+                return Mode::Normal;
+            }
+            KeyCode::Up | KeyCode::Char('k') => grep.move_up(),
+            KeyCode::Down | KeyCode::Char('j') => grep.move_down(),
+            KeyCode::Char('g') => grep.go_top(),
+            KeyCode::Char('G') => grep.go_bottom(),
+            KeyCode::Enter | KeyCode::Char('l') => app.open_grep_hit(),
+            _ => {}
+        }
+
+        Mode::Grep
+    }
+
+    fn handle_buffer(&mut self, app: &mut App, key_event: KeyEvent) -> Mode {
+        if app.buffer_plan_pending() {
+            match key_event.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => app.apply_buffer_plan(),
+                _ => app.discard_buffer_plan(),
+            }
+        } else {
+            app.clear_msg();
+            if !app.buffer_key(key_event) {
+                return Mode::Normal;
+            }
+        }
+
+        match app.buffer {
+            Some(_) => Mode::Buffer,
+            None => Mode::Normal,
+        }
+    }
+
+    fn handle_input(&mut self, app: &mut App, action: Action, key_event: KeyEvent) -> Mode {
+        match key_event.code {
+            KeyCode::Esc => {
+                app.clear_msg();
+                match action {
+                    Action::Search => app.cancel_search(),
+                    Action::Filter => app.cancel_filter(),
+                    _ => {}
+                }
+                return Mode::Normal;
+            }
             KeyCode::Char(c) => app.insert_char(c),
-            KeyCode::Right => app.move_into(),
-            KeyCode::Left => app.move_back(),
+            KeyCode::Right if matches!(action, Action::Rename) => app.move_into(),
+            KeyCode::Left if matches!(action, Action::Rename) => app.move_back(),
             KeyCode::Backspace => app.delete_char(),
             KeyCode::Enter => {
-                mode = Mode::Normal;
-                app.terminate_input();
+                app.clear_msg();
+                return match action {
+                    Action::Command => {
+                        let line = app.take_input().unwrap_or_default();
+                        self.run_prompt(app, &line)
+                    }
+                    Action::Search => {
+                        app.confirm_search();
+                        Mode::Normal
+                    }
+                    Action::Filter => {
+                        app.confirm_filter();
+                        Mode::Normal
+                    }
+                    Action::Grep => {
+                        if app.start_grep() {
+                            Mode::Grep
+                        } else {
+                            Mode::Normal
+                        }
+                    }
+                    Action::Extract => {
+                        app.extract();
+                        Mode::Normal
+                    }
+                    _ => {
+                        app.terminate_input();
+                        Mode::Normal
+                    }
+                };
             }
             _ => {}
         }
 
-        mode
+        // search and filter follow along as the pattern is typed
+        match action {
+            Action::Search => app.update_search(),
+            Action::Filter => app.update_filter(),
+            _ => {}
+        }
+
+        Mode::Action(action)
+    }
+}
+
+/// Trashing and deleting go back to wherever they were asked for from.
+fn removal_done(app: &App) -> Mode {
+    if app.usage.is_some() {
+        Mode::Usage
+    } else {
+        Mode::Normal
     }
 }
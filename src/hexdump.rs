@@ -0,0 +1,131 @@
+// xxd-style preview for files that aren't text
+
+use std::io::{self, Read};
+
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+
+/// xxd's own width, wider panes don't get more than this.
+const MAX_ROW_BYTES: usize = 16;
+
+/// One row per line of `height`, as many bytes per row as fit in `width`.
+/// Only the bytes that end up on screen are read.
+pub fn dump(reader: impl Read, width: usize, height: usize) -> io::Result<Vec<Line<'static>>> {
+    let row_bytes = row_bytes(width);
+    let mut bytes = Vec::with_capacity(row_bytes * height);
+    reader
+        .take((row_bytes * height) as u64)
+        .read_to_end(&mut bytes)?;
+
+    Ok(dump_bytes(&bytes, width, height))
+}
+
+/// Same as [`dump`], for bytes that are already in memory.
+pub fn dump_bytes(bytes: &[u8], width: usize, height: usize) -> Vec<Line<'static>> {
+    let row_bytes = row_bytes(width);
+    bytes
+        .chunks(row_bytes)
+        .take(height)
+        .enumerate()
+        .map(|(row, chunk)| format_row(row * row_bytes, chunk, row_bytes))
+        .collect()
+}
+
+/// `00000010: 4865 6c6c 6f0a  Hello.` takes 11 columns for the offset and
+/// separators, 2.5 per byte for the hex and 1 per byte for the text. Rows
+/// stay a power of two wide so the offsets are easy to follow.
+fn row_bytes(width: usize) -> usize {
+    let fits = width.saturating_sub(11) * 2 / 7;
+    let mut row_bytes = MAX_ROW_BYTES;
+    while row_bytes > 2 && row_bytes > fits {
+        row_bytes /= 2;
+    }
+    row_bytes
+}
+
+fn format_row(offset: usize, chunk: &[u8], row_bytes: usize) -> Line<'static> {
+    let mut spans = vec![Span::styled(
+        format!("{:08x}: ", offset),
+        Style::default().fg(Color::DarkGray),
+    )];
+
+    for (i, pair) in chunk.chunks(2).enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" "));
+        }
+        for byte in pair {
+            spans.push(Span::styled(format!("{:02x}", byte), byte_style(*byte)));
+        }
+    }
+
+    // a short last row still lines its text up with the rows above
+    let missing = row_bytes - chunk.len();
+    let padding = missing * 2 + row_bytes.div_ceil(2) - chunk.len().div_ceil(2);
+    spans.push(Span::raw(" ".repeat(padding + 2)));
+
+    for byte in chunk {
+        let ch = match byte {
+            0x20..=0x7e => *byte as char,
+            _ => '.',
+        };
+        spans.push(Span::styled(ch.to_string(), byte_style(*byte)));
+    }
+
+    Line::from(spans)
+}
+
+fn byte_style(byte: u8) -> Style {
+    let color = match byte {
+        0 => Color::DarkGray,
+        0x20..=0x7e => Color::White,
+        _ => Color::Yellow,
+    };
+    Style::default().fg(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn rows_look_like_xxd() {
+        let lines = dump_bytes(b"Hello, world!\n\0\xffmore", 80, 10);
+        assert_eq!(
+            text(&lines),
+            [
+                "00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a 00ff  Hello, world!...",
+                "00000010: 6d6f 7265                                more",
+            ]
+        );
+    }
+
+    #[test]
+    fn narrow_panes_get_fewer_bytes_per_row() {
+        assert_eq!(row_bytes(200), 16);
+        assert_eq!(row_bytes(67), 16);
+        assert_eq!(row_bytes(66), 8);
+        assert_eq!(row_bytes(0), 2);
+
+        // every row, short or not, fits the width it was made for
+        for width in [20, 39, 40, 66, 67] {
+            for line in dump_bytes(&[b'a'; 100], width, 100) {
+                assert!(line.width() <= width.max(18), "{width}: {line}");
+            }
+        }
+    }
+
+    #[test]
+    fn reads_only_what_fits() {
+        let data = vec![0u8; 1000];
+        let lines = dump(&data[..], 80, 3).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].to_string().starts_with("00000020: "));
+        assert!(dump(&[][..], 80, 3).unwrap().is_empty());
+    }
+}
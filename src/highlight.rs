@@ -0,0 +1,126 @@
+// syntax highlighting for the preview pane. It's only ever handed the lines
+// that fit on screen, so a huge source file costs as much as a small one
+
+use std::path::Path;
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: load_theme(DEFAULT_THEME).expect("bundled theme"),
+        }
+    }
+}
+
+impl Highlighter {
+    pub fn set_theme(&mut self, name: &str) -> Result<(), String> {
+        self.theme = load_theme(name)?;
+        Ok(())
+    }
+
+    /// Colours the first lines of `path`, `lines` keep their line endings.
+    /// Files nobody knows the syntax of come back as plain white text.
+    pub fn highlight(&self, path: &Path, lines: &[String]) -> Vec<Line<'static>> {
+        let Some(syntax) = self.syntax_for(path, lines.first()) else {
+            return lines
+                .iter()
+                .map(|line| Line::styled(untab(line), Style::default().fg(Color::White)))
+                .collect();
+        };
+
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        lines
+            .iter()
+            .map(|line| {
+                let Ok(regions) = highlighter.highlight_line(line, &self.syntaxes) else {
+                    return Line::from(untab(line));
+                };
+                regions
+                    .into_iter()
+                    .map(|(style, text)| Span::styled(untab(text), convert(style)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// By extension first (or the whole name, for things like `Makefile`),
+    /// then by the first line, which covers shebangs.
+    fn syntax_for(&self, path: &Path, first_line: Option<&String>) -> Option<&SyntaxReference> {
+        let by_name = [path.extension(), path.file_name()]
+            .into_iter()
+            .flatten()
+            .filter_map(|s| s.to_str())
+            .find_map(|s| self.syntaxes.find_syntax_by_extension(s));
+
+        by_name
+            .or_else(|| self.syntaxes.find_syntax_by_first_line(first_line?))
+            .or_else(|| {
+                self.syntaxes
+                    .find_syntax_by_token(interpreter(first_line?)?)
+            })
+            .filter(|syntax| syntax.name != "Plain Text")
+    }
+}
+
+/// One of the bundled themes or a path to a `.tmTheme` file.
+pub fn load_theme(name: &str) -> Result<Theme, String> {
+    if name.ends_with(".tmTheme") {
+        return ThemeSet::get_theme(name).map_err(|e| format!("{}: {}", name, e));
+    }
+
+    let mut themes = ThemeSet::load_defaults().themes;
+    themes.remove(name).ok_or_else(|| {
+        let names: Vec<_> = themes.keys().map(String::as_str).collect();
+        let names = names.join(", ");
+        format!("unknown theme \"{}\", try one of: {}", name, names)
+    })
+}
+
+/// `#!/usr/bin/env python3` -> `python`, for shebangs none of the syntaxes
+/// recognise by themselves.
+fn interpreter(line: &str) -> Option<&str> {
+    let command = line.strip_prefix("#!")?.split_whitespace();
+    let mut words = command.filter(|w| !w.starts_with('-'));
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.next()?;
+    }
+    Some(program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.'))
+}
+
+fn untab(text: &str) -> String {
+    text.trim_end_matches(['\n', '\r']).replace('\t', "    ")
+}
+
+fn convert(style: syntect::highlighting::Style) -> Style {
+    let fg = style.foreground;
+    let mut converted = Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b));
+    for (font, modifier) in [
+        (FontStyle::BOLD, Modifier::BOLD),
+        (FontStyle::ITALIC, Modifier::ITALIC),
+        (FontStyle::UNDERLINE, Modifier::UNDERLINED),
+    ] {
+        if style.font_style.contains(font) {
+            converted = converted.add_modifier(modifier);
+        }
+    }
+    converted
+}
@@ -0,0 +1,525 @@
+// file operations that may take a while run on tokio's blocking pool and
+// report back through the EventHandler channel, so the UI never waits on them
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    archive,
+    buffer::{self, Change},
+    event::Event,
+    fs_ops,
+    journal::Operation,
+    trash::Trash,
+};
+
+pub type JobId = usize;
+
+const CHUNK_SIZE: usize = 256 * 1024;
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub enum JobTask {
+    Copy {
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+    },
+    Move {
+        sources: Vec<PathBuf>,
+        dest: PathBuf,
+    },
+    Trash {
+        paths: Vec<PathBuf>,
+    },
+    Delete {
+        paths: Vec<PathBuf>,
+    },
+    /// A confirmed directory buffer plan.
+    Plan {
+        changes: Vec<Change>,
+    },
+    /// Members of `archive`, relative to its root, and how many files they
+    /// hold between them.
+    Extract {
+        archive: PathBuf,
+        members: Vec<PathBuf>,
+        dest: PathBuf,
+        files: u64,
+    },
+}
+
+impl JobTask {
+    fn verb(&self) -> &'static str {
+        match self {
+            JobTask::Copy { .. } => "Copying",
+            JobTask::Move { .. } => "Moving",
+            JobTask::Trash { .. } => "Trashing",
+            JobTask::Delete { .. } => "Deleting",
+            JobTask::Plan { .. } => "Applying",
+            JobTask::Extract { .. } => "Extracting",
+        }
+    }
+
+    fn past(&self) -> &'static str {
+        match self {
+            JobTask::Copy { .. } => "Copied",
+            JobTask::Move { .. } => "Moved",
+            JobTask::Trash { .. } => "Trashed",
+            JobTask::Delete { .. } => "Deleted",
+            JobTask::Plan { .. } => "Applied",
+            JobTask::Extract { .. } => "Extracted",
+        }
+    }
+
+    fn label(&self) -> String {
+        let paths = match self {
+            JobTask::Copy { sources, .. } | JobTask::Move { sources, .. } => sources,
+            JobTask::Trash { paths } | JobTask::Delete { paths } => paths,
+            JobTask::Extract { members, .. } => members,
+            JobTask::Plan { changes } => {
+                return format!("{} changes", changes.len());
+            }
+        };
+
+        match paths.as_slice() {
+            [single] => fs_ops::display_name(single),
+            many => format!("{} entries", many.len()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Progress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+}
+
+impl Progress {
+    /// Estimated time left, going by bytes when there are any to go by.
+    pub fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        let (done, total) = if self.bytes_total > 0 {
+            (self.bytes_done, self.bytes_total)
+        } else {
+            (self.files_done, self.files_total)
+        };
+
+        if done == 0 || done >= total {
+            return None;
+        }
+
+        let secs = elapsed.as_secs_f64() * (total - done) as f64 / done as f64;
+        Some(Duration::from_secs_f64(secs))
+    }
+}
+
+#[derive(Debug)]
+pub enum JobEvent {
+    Progress {
+        id: JobId,
+        progress: Progress,
+    },
+    Done {
+        id: JobId,
+        ops: Vec<Operation>,
+        errors: Vec<String>,
+        cancelled: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct Job {
+    pub id: JobId,
+    pub verb: &'static str,
+    pub past: &'static str,
+    pub label: String,
+    pub progress: Progress,
+    pub started: Instant,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+/// Running jobs, in the order they were started.
+#[derive(Debug)]
+pub struct JobQueue {
+    pub jobs: Vec<Job>,
+    next_id: JobId,
+    sender: UnboundedSender<Event>,
+}
+
+impl JobQueue {
+    pub fn new(sender: UnboundedSender<Event>) -> Self {
+        Self {
+            jobs: Vec::new(),
+            next_id: 0,
+            sender,
+        }
+    }
+
+    pub fn spawn(&mut self, task: JobTask, trash: Option<Trash>) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.jobs.push(Job {
+            id,
+            verb: task.verb(),
+            past: task.past(),
+            label: task.label(),
+            progress: Progress::default(),
+            started: Instant::now(),
+            cancel: cancel.clone(),
+        });
+
+        let worker = Worker {
+            id,
+            cancel,
+            sender: self.sender.clone(),
+            trash,
+            progress: Progress::default(),
+            last_report: Instant::now(),
+            ops: Vec::new(),
+            errors: Vec::new(),
+        };
+        tokio::task::spawn_blocking(move || worker.run(task));
+
+        id
+    }
+
+    pub fn cancel(&self, id: JobId) {
+        if let Some(job) = self.jobs.iter().find(|j| j.id == id) {
+            job.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn update(&mut self, id: JobId, progress: Progress) {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            job.progress = progress;
+        }
+    }
+
+    pub fn finish(&mut self, id: JobId) -> Option<Job> {
+        let idx = self.jobs.iter().position(|j| j.id == id)?;
+        Some(self.jobs.remove(idx))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+struct Worker {
+    id: JobId,
+    cancel: Arc<AtomicBool>,
+    sender: UnboundedSender<Event>,
+    trash: Option<Trash>,
+    progress: Progress,
+    last_report: Instant,
+    ops: Vec<Operation>,
+    errors: Vec<String>,
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
+
+impl Worker {
+    fn run(mut self, task: JobTask) {
+        match task {
+            JobTask::Copy { sources, dest } => {
+                for source in &sources {
+                    let (bytes, files) = measure(source);
+                    self.progress.bytes_total += bytes;
+                    self.progress.files_total += files;
+                }
+                self.each(&sources, |w, source| w.copy_into(source, &dest));
+            }
+            JobTask::Move { sources, dest } => {
+                self.progress.files_total = sources.len() as u64;
+                self.each(&sources, |w, source| w.move_into(source, &dest));
+            }
+            JobTask::Trash { paths } => {
+                self.progress.files_total = paths.len() as u64;
+                self.each(&paths, |w, path| {
+                    let trash = w.trash.as_ref().ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::NotFound, "no trash directory available")
+                    })?;
+                    let entry = trash.put(path)?;
+                    w.ops.push(Operation::Trash { entry });
+                    w.file_done(0);
+                    Ok(())
+                });
+            }
+            JobTask::Delete { paths } => {
+                for path in &paths {
+                    self.progress.files_total += measure(path).1;
+                }
+                self.each(&paths, |w, path| w.remove_tree(path));
+            }
+            JobTask::Plan { changes } => {
+                self.progress.files_total = changes.len() as u64;
+                let (ops, error) =
+                    buffer::execute(&changes, self.trash.clone().as_ref(), &mut || {
+                        self.check()?;
+                        self.file_done(0);
+                        Ok(())
+                    });
+                self.ops = ops;
+                self.errors.extend(error);
+            }
+            JobTask::Extract {
+                archive,
+                members,
+                dest,
+                files,
+            } => {
+                self.progress.files_total = files;
+                self.extract(&archive, &members, &dest);
+            }
+        }
+
+        let cancelled = self.cancel.load(Ordering::Relaxed);
+        let _ = self.sender.send(Event::Job(JobEvent::Done {
+            id: self.id,
+            ops: self.ops,
+            errors: self.errors,
+            cancelled,
+        }));
+    }
+
+    /// Runs `op` on every path, stopping early only when cancelled.
+    fn each<F>(&mut self, paths: &[PathBuf], mut op: F)
+    where
+        F: FnMut(&mut Self, &Path) -> io::Result<()>,
+    {
+        for path in paths {
+            match op(self, path) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => break,
+                Err(e) => self
+                    .errors
+                    .push(format!("{}: {}", fs_ops::display_name(path), e)),
+            }
+        }
+        self.report(true);
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.cancel.load(Ordering::Relaxed) {
+            Err(cancelled())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn report(&mut self, force: bool) {
+        if force || self.last_report.elapsed() >= REPORT_INTERVAL {
+            self.last_report = Instant::now();
+            let _ = self.sender.send(Event::Job(JobEvent::Progress {
+                id: self.id,
+                progress: self.progress,
+            }));
+        }
+    }
+
+    fn file_done(&mut self, bytes: u64) {
+        self.progress.files_done += 1;
+        self.progress.bytes_done += bytes;
+        self.report(false);
+    }
+
+    fn copy_into(&mut self, source: &Path, dest: &Path) -> io::Result<()> {
+        if dest.starts_with(source) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can't copy a directory into itself",
+            ));
+        }
+
+        let name = source.file_name().unwrap_or(source.as_os_str());
+        let to = fs_ops::unique_path(dest, name);
+        match self.copy_tree(source, &to) {
+            Ok(_) => {
+                self.ops.push(Operation::Copy {
+                    from: source.to_path_buf(),
+                    to,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                // don't leave half a copy behind
+                let _ = fs_ops::remove_path(&to);
+                Err(e)
+            }
+        }
+    }
+
+    fn move_into(&mut self, source: &Path, dest: &Path) -> io::Result<()> {
+        if dest.starts_with(source) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can't move a directory into itself",
+            ));
+        }
+        // already where it's supposed to go
+        if source.parent() == Some(dest) {
+            self.file_done(0);
+            return Ok(());
+        }
+
+        let name = source.file_name().unwrap_or(source.as_os_str());
+        let to = fs_ops::unique_path(dest, name);
+        match fs::rename(source, &to) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                // no cheap rename across filesystems, copy with progress instead
+                let (bytes, files) = measure(source);
+                self.progress.bytes_total += bytes;
+                self.progress.files_total += files.saturating_sub(1);
+                if let Err(e) = self.copy_tree(source, &to) {
+                    let _ = fs_ops::remove_path(&to);
+                    return Err(e);
+                }
+                fs_ops::remove_path(source)?;
+            }
+            Err(e) => return Err(e),
+            Ok(_) => self.file_done(0),
+        }
+
+        self.ops.push(Operation::Move {
+            from: source.to_path_buf(),
+            to,
+        });
+        Ok(())
+    }
+
+    fn copy_tree(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check()?;
+        let meta = fs::symlink_metadata(from)?;
+
+        if meta.is_dir() {
+            fs::create_dir(to)?;
+            for entry in fs::read_dir(from)? {
+                let entry = entry?;
+                self.copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+            }
+            fs::set_permissions(to, meta.permissions())?;
+            self.file_done(0);
+            Ok(())
+        } else if meta.is_symlink() {
+            fs_ops::copy_recursive(from, to)?;
+            self.file_done(0);
+            Ok(())
+        } else {
+            self.copy_file(from, to, meta.permissions())
+        }
+    }
+
+    fn copy_file(&mut self, from: &Path, to: &Path, perms: fs::Permissions) -> io::Result<()> {
+        let mut reader = File::open(from)?;
+        let mut writer = File::create_new(to)?;
+        let mut buf = vec![0; CHUNK_SIZE];
+
+        loop {
+            self.check()?;
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buf[..n])?;
+            self.progress.bytes_done += n as u64;
+            self.report(false);
+        }
+
+        writer.set_permissions(perms)?;
+        self.file_done(0);
+        Ok(())
+    }
+
+    fn extract(&mut self, archive: &Path, members: &[PathBuf], dest: &Path) {
+        let targets: Vec<_> = members
+            .iter()
+            .map(|member| {
+                let name = member.file_name().unwrap_or(member.as_os_str());
+                (member.clone(), fs_ops::unique_path(dest, name))
+            })
+            .collect();
+
+        let result = archive::extract(archive, &targets, |bytes| {
+            self.check()?;
+            self.file_done(bytes);
+            Ok(())
+        });
+        match result {
+            Ok(_) => self
+                .ops
+                .extend(targets.into_iter().map(|(member, to)| Operation::Extract {
+                    archive: archive.to_path_buf(),
+                    member,
+                    to,
+                })),
+            Err(e) => {
+                // don't leave half of it behind
+                for (_, to) in &targets {
+                    let _ = fs_ops::remove_path(to);
+                }
+                if e.kind() != io::ErrorKind::Interrupted {
+                    self.errors
+                        .push(format!("{}: {}", fs_ops::display_name(archive), e));
+                }
+            }
+        }
+        self.report(true);
+    }
+
+    fn remove_tree(&mut self, path: &Path) -> io::Result<()> {
+        self.check()?;
+        let meta = fs::symlink_metadata(path)?;
+
+        if meta.is_dir() {
+            for entry in fs::read_dir(path)? {
+                self.remove_tree(&entry?.path())?;
+            }
+            fs::remove_dir(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+
+        self.file_done(0);
+        Ok(())
+    }
+}
+
+/// Total size and entry count below `path`, without following symlinks.
+fn measure(path: &Path) -> (u64, u64) {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return (0, 0);
+    };
+
+    if !meta.is_dir() {
+        return (meta.len(), 1);
+    }
+
+    fs::read_dir(path)
+        .map(|dir| {
+            dir.filter_map(|e| e.ok())
+                .map(|e| measure(&e.path()))
+                .fold((0, 1), |(bytes, files), (b, f)| (bytes + b, files + f))
+        })
+        .unwrap_or((0, 1))
+}
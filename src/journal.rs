@@ -0,0 +1,398 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    archive, fs_ops,
+    trash::{Trash, TrashEntry},
+};
+
+/// A single reversible filesystem operation.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// Rename or move, `from` no longer exists afterwards.
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Copy {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Trash {
+        entry: TrashEntry,
+    },
+    Create {
+        path: PathBuf,
+        dir: bool,
+    },
+    /// `member` is relative to the archive's root.
+    Extract {
+        archive: PathBuf,
+        member: PathBuf,
+        to: PathBuf,
+    },
+}
+
+/// Cheap snapshot used to tell whether something was touched since the
+/// journal last saw it.
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint {
+    len: u64,
+    modified: Option<SystemTime>,
+    dir: bool,
+}
+
+impl Fingerprint {
+    fn of(path: &Path) -> Option<Self> {
+        fs::symlink_metadata(path).ok().map(|m| Self {
+            len: m.len(),
+            modified: m.modified().ok(),
+            dir: m.is_dir(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Step {
+    op: Operation,
+    /// State of whatever the operation last produced: the target after
+    /// doing it, the source after undoing it.
+    seen: Option<Fingerprint>,
+}
+
+/// Ops that happened together (e.g. trashing a whole selection) and are
+/// undone together.
+#[derive(Debug, Clone)]
+struct Transaction {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Default)]
+pub struct Journal {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+}
+
+impl Operation {
+    /// Where the operation put things.
+    fn target(&self) -> &Path {
+        match self {
+            Operation::Move { to, .. } | Operation::Copy { to, .. } => to,
+            Operation::Trash { entry } => &entry.trashed,
+            Operation::Create { path, .. } => path,
+            Operation::Extract { to, .. } => to,
+        }
+    }
+
+    /// Where things were before the operation, if it consumed anything.
+    fn source(&self) -> Option<&Path> {
+        match self {
+            Operation::Move { from, .. } => Some(from),
+            Operation::Trash { entry } => Some(&entry.original),
+            Operation::Copy { .. } | Operation::Create { .. } | Operation::Extract { .. } => None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Operation::Move { from, to } if from.parent() == to.parent() => format!(
+                "rename {} → {}",
+                fs_ops::display_name(from),
+                fs_ops::display_name(to)
+            ),
+            Operation::Move { from, to } => {
+                format!("move {} → {}", fs_ops::display_name(from), to.display())
+            }
+            Operation::Copy { from, to } => {
+                format!("copy {} → {}", fs_ops::display_name(from), to.display())
+            }
+            Operation::Trash { entry } => format!("trash {}", entry.original.display()),
+            Operation::Create { path, .. } => format!("create {}", fs_ops::display_name(path)),
+            Operation::Extract { member, to, .. } => {
+                format!(
+                    "extract {} → {}",
+                    fs_ops::display_name(member),
+                    to.display()
+                )
+            }
+        }
+    }
+
+    fn undo(&self, trash: Option<&Trash>) -> io::Result<()> {
+        match self {
+            Operation::Move { from, to } => {
+                refuse_if_occupied(from)?;
+                fs_ops::move_path(to, from)
+            }
+            // trashed rather than removed, the fingerprint only covers the top
+            // of a directory so whatever changed further down is kept
+            Operation::Copy { to, .. } | Operation::Extract { to, .. } => {
+                no_trash(trash)?.put(to).map(|_| ())
+            }
+            Operation::Trash { entry } => no_trash(trash)?.restore(entry),
+            Operation::Create { path, dir: true } => fs::remove_dir(path),
+            Operation::Create { path, dir: false } => fs::remove_file(path),
+        }
+    }
+
+    fn redo(&mut self, trash: Option<&Trash>) -> io::Result<()> {
+        match self {
+            Operation::Move { from, to } => {
+                refuse_if_occupied(to)?;
+                fs_ops::move_path(from, to)
+            }
+            Operation::Copy { from, to } => {
+                refuse_if_occupied(to)?;
+                fs_ops::copy_recursive(from, to)
+            }
+            Operation::Trash { entry } => {
+                *entry = no_trash(trash)?.put(&entry.original)?;
+                Ok(())
+            }
+            Operation::Extract {
+                archive,
+                member,
+                to,
+            } => {
+                refuse_if_occupied(to)?;
+                archive::extract(archive, &[(member.clone(), to.clone())], |_| Ok(()))
+            }
+            Operation::Create { path, dir } => {
+                refuse_if_occupied(path)?;
+                if *dir {
+                    fs::create_dir(path)
+                } else {
+                    fs::File::create_new(path).map(|_| ())
+                }
+            }
+        }
+    }
+}
+
+fn refuse_if_occupied(path: &Path) -> io::Result<()> {
+    if fs_ops::occupied(path) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn no_trash(trash: Option<&Trash>) -> io::Result<&Trash> {
+    trash.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no trash directory available"))
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a batch of operations that just succeeded, dropping the redo
+    /// history like any editor would.
+    pub fn record(&mut self, ops: Vec<Operation>) {
+        if ops.is_empty() {
+            return;
+        }
+
+        let steps = ops
+            .into_iter()
+            .map(|op| Step {
+                seen: Fingerprint::of(op.target()),
+                op,
+            })
+            .collect();
+
+        self.undo.push(Transaction { steps });
+        self.redo.clear();
+    }
+
+    /// Reverts the last transaction, returning a message for the user.
+    pub fn undo(&mut self, trash: Option<&Trash>) -> String {
+        let Some(mut transaction) = self.undo.pop() else {
+            return "Already at oldest change".to_string();
+        };
+
+        // refuse before touching anything if some target changed under us
+        let modified = transaction.steps.iter().find(|step| {
+            Fingerprint::of(step.op.target()) != step.seen
+                && !matches!(step.op, Operation::Trash { .. })
+        });
+        if let Some(step) = modified {
+            let msg = format!(
+                "Can't undo {}: {} was modified since",
+                step.op.describe(),
+                step.op.target().display()
+            );
+            self.undo.push(transaction);
+            return msg;
+        }
+
+        let mut done = Vec::new();
+        while let Some(mut step) = transaction.steps.pop() {
+            if let Err(e) = step.op.undo(trash) {
+                let msg = format!("Can't undo {}: {}", step.op.describe(), e);
+                transaction.steps.push(step);
+                self.undo.push(transaction);
+                self.push_redo(done);
+                return msg;
+            }
+
+            step.seen = step.op.source().and_then(Fingerprint::of);
+            done.push(step);
+        }
+
+        let msg = summarize("Undid", &done);
+        self.push_redo(done);
+        msg
+    }
+
+    /// Replays the last undone transaction, returning a message for the user.
+    pub fn redo(&mut self, trash: Option<&Trash>) -> String {
+        let Some(mut transaction) = self.redo.pop() else {
+            return "Already at newest change".to_string();
+        };
+
+        let modified = transaction.steps.iter().find(|step| {
+            step.op
+                .source()
+                .is_some_and(|source| Fingerprint::of(source) != step.seen)
+        });
+        if let Some(step) = modified {
+            let msg = format!(
+                "Can't redo {}: {} was modified since",
+                step.op.describe(),
+                step.op.source().unwrap_or(step.op.target()).display()
+            );
+            self.redo.push(transaction);
+            return msg;
+        }
+
+        let mut done = Vec::new();
+        while let Some(mut step) = transaction.steps.pop() {
+            if let Err(e) = step.op.redo(trash) {
+                let msg = format!("Can't redo {}: {}", step.op.describe(), e);
+                transaction.steps.push(step);
+                self.redo.push(transaction);
+                self.push_undo(done);
+                return msg;
+            }
+
+            step.seen = Fingerprint::of(step.op.target());
+            done.push(step);
+        }
+
+        let msg = summarize("Redid", &done);
+        self.push_undo(done);
+        msg
+    }
+
+    // steps come out reversed, so a full round trip restores the original order
+    fn push_redo(&mut self, steps: Vec<Step>) {
+        if !steps.is_empty() {
+            self.redo.push(Transaction { steps });
+        }
+    }
+
+    fn push_undo(&mut self, steps: Vec<Step>) {
+        if !steps.is_empty() {
+            self.undo.push(Transaction { steps });
+        }
+    }
+}
+
+fn summarize(verb: &str, steps: &[Step]) -> String {
+    match steps {
+        [step] => format!("{} {}", verb, step.op.describe()),
+        steps => format!("{} {} changes", verb, steps.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("fear-journal-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn moved(dir: &Path) -> (PathBuf, PathBuf, Journal) {
+        let (from, to) = (dir.join("a"), dir.join("b"));
+        fs::write(&from, "a").unwrap();
+        fs::rename(&from, &to).unwrap();
+        let mut journal = Journal::new();
+        journal.record(vec![Operation::Move {
+            from: from.clone(),
+            to: to.clone(),
+        }]);
+        (from, to, journal)
+    }
+
+    #[test]
+    fn move_round_trips() {
+        let scratch = Scratch::new("round-trip");
+        let (from, to, mut journal) = moved(&scratch.0);
+
+        assert_eq!(journal.undo(None), "Undid rename a → b");
+        assert!(from.exists() && !to.exists());
+        assert_eq!(journal.undo(None), "Already at oldest change");
+
+        assert_eq!(journal.redo(None), "Redid rename a → b");
+        assert!(!from.exists() && to.exists());
+        assert_eq!(journal.redo(None), "Already at newest change");
+    }
+
+    #[test]
+    fn undo_refuses_a_modified_target() {
+        let scratch = Scratch::new("modified-target");
+        let (from, to, mut journal) = moved(&scratch.0);
+
+        fs::write(&to, "changed").unwrap();
+        assert!(journal.undo(None).starts_with("Can't undo"));
+        assert!(!from.exists() && to.exists());
+        // kept for another try rather than dropped
+        assert_eq!(journal.undo.len(), 1);
+    }
+
+    #[test]
+    fn redo_refuses_a_modified_source() {
+        let scratch = Scratch::new("modified-source");
+        let (from, to, mut journal) = moved(&scratch.0);
+
+        journal.undo(None);
+        fs::write(&from, "changed").unwrap();
+        assert!(journal.redo(None).starts_with("Can't redo"));
+        assert!(from.exists() && !to.exists());
+    }
+
+    #[test]
+    fn recording_drops_the_redo_history() {
+        let scratch = Scratch::new("drop-redo");
+        let (_, _, mut journal) = moved(&scratch.0);
+
+        journal.undo(None);
+        let path = scratch.0.join("c");
+        fs::create_dir(&path).unwrap();
+        journal.record(vec![Operation::Create { path, dir: true }]);
+        assert_eq!(journal.redo(None), "Already at newest change");
+        assert_eq!(journal.undo(None), "Undid create c");
+        assert!(!scratch.0.join("c").exists());
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+use crate::{command::Command, config::LuaFn};
+
+/// Shipped bindings, the user's keymap.toml only needs to say what differs.
+const DEFAULT_KEYMAP: &str = include_str!("../assets/keymap.toml");
+
+/// A single key press, as written in bindings: `x`, `G`, `<C-r>`, `<Space>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn from_event(key_event: &KeyEvent) -> Self {
+        let mut modifiers = key_event.modifiers;
+        // the case of the char already says whether shift was held
+        if let KeyCode::Char(_) = key_event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+
+        Self {
+            code: key_event.code,
+            modifiers,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid key \"{}\"", text);
+
+        let mut chars = text.chars();
+        if let (Some(ch), None) = (chars.next(), chars.next()) {
+            return Ok(Self {
+                code: KeyCode::Char(ch),
+                modifiers: KeyModifiers::NONE,
+            });
+        }
+
+        let inner = text
+            .strip_prefix('<')
+            .and_then(|t| t.strip_suffix('>'))
+            .filter(|t| !t.is_empty())
+            .ok_or_else(invalid)?;
+
+        let mut modifiers = KeyModifiers::NONE;
+        let mut name = inner;
+        // `<C-->` is ctrl and minus, so only split off prefixes while
+        // something is left after them
+        while let Some((prefix, rest)) = name.split_once('-').filter(|(_, r)| !r.is_empty()) {
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "c" => KeyModifiers::CONTROL,
+                "a" | "m" => KeyModifiers::ALT,
+                "s" => KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            };
+            name = rest;
+        }
+
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(ch), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(ch.to_ascii_uppercase())
+            }
+            (Some(ch), None) => KeyCode::Char(ch),
+            _ => named_key(name).ok_or_else(invalid)?,
+        };
+
+        Ok(Self { code, modifiers })
+    }
+}
+
+/// Splits `gg` or `<C-w>j` into the chords typed one after the other.
+pub fn parse_sequence(text: &str) -> Result<Vec<KeyChord>, String> {
+    let mut chords = Vec::new();
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        let len = match rest.find('>') {
+            Some(end) if ch == '<' && end > 1 => end + 1,
+            _ => ch.len_utf8(),
+        };
+        chords.push(KeyChord::parse(&rest[..len])?);
+        rest = &rest[len..];
+    }
+
+    if chords.is_empty() {
+        Err("empty key".to_string())
+    } else {
+        Ok(chords)
+    }
+}
+
+/// What a key sequence is bound to.
+#[derive(Debug, Clone, Copy)]
+pub enum Binding {
+    Command(Command),
+    Lua(LuaFn),
+}
+
+pub enum Lookup {
+    Found(Binding),
+    /// The keys so far start a longer sequence, wait for more.
+    Pending,
+    NotFound,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+    #[serde(default)]
+    normal: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct Keymap {
+    normal: HashMap<Vec<KeyChord>, Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            normal: HashMap::new(),
+        };
+        let errors = keymap.load(DEFAULT_KEYMAP, "default keymap");
+        assert!(errors.is_empty(), "{}", errors.join("\n"));
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Merges a keymap file on top of the current bindings, returning one
+    /// error per entry that couldn't be used.
+    pub fn load(&mut self, source: &str, name: &str) -> Vec<String> {
+        let file: KeymapFile = match toml::from_str(source) {
+            Ok(file) => file,
+            Err(e) => {
+                let line = e.message().lines().next().unwrap_or_default().to_string();
+                return vec![format!("{}: {}", name, line)];
+            }
+        };
+
+        let mut errors = Vec::new();
+        for (keys, action) in file.normal {
+            let chords = match parse_sequence(&keys) {
+                Ok(chords) => chords,
+                Err(e) => {
+                    errors.push(format!("{}: {}", name, e));
+                    continue;
+                }
+            };
+
+            if action == "none" {
+                self.normal.remove(&chords);
+                continue;
+            }
+            match Command::from_name(&action) {
+                Some(command) => self.bind(chords, Binding::Command(command)),
+                None => errors.push(format!(
+                    "{}: unknown action \"{}\" for \"{}\"",
+                    name, action, keys
+                )),
+            }
+        }
+        errors
+    }
+
+    pub fn bind(&mut self, keys: Vec<KeyChord>, binding: Binding) {
+        self.normal.insert(keys, binding);
+    }
+
+    pub fn lookup(&self, keys: &[KeyChord]) -> Lookup {
+        if let Some(binding) = self.normal.get(keys) {
+            return Lookup::Found(*binding);
+        }
+
+        let longer = self
+            .normal
+            .keys()
+            .any(|k| k.len() > keys.len() && k.starts_with(keys));
+        if longer {
+            Lookup::Pending
+        } else {
+            Lookup::NotFound
+        }
+    }
+
+    /// Sequences that can never fire because a prefix of them is bound too.
+    pub fn shadowed(&self) -> Vec<String> {
+        let mut shadowed: Vec<_> = self
+            .normal
+            .keys()
+            .filter(|keys| (1..keys.len()).any(|n| self.normal.contains_key(&keys[..n])))
+            .map(|keys| {
+                let keys: String = keys.iter().map(KeyChord::to_string).collect();
+                format!("\"{}\" is unreachable, a shorter prefix is bound", keys)
+            })
+            .collect();
+        shadowed.sort();
+        shadowed
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char('<') => "lt".to_string(),
+            KeyCode::Char(ch) => ch.to_string(),
+            KeyCode::Enter => "CR".to_string(),
+            KeyCode::Backspace => "BS".to_string(),
+            KeyCode::Delete => "Del".to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            code => format!("{:?}", code),
+        };
+
+        let mut modifiers = String::new();
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                modifiers.push_str(prefix);
+            }
+        }
+
+        if modifiers.is_empty() && name.chars().count() == 1 {
+            write!(f, "{}", name)
+        } else {
+            write!(f, "<{}{}>", modifiers, name)
+        }
+    }
+}
+
+fn named_key(name: &str) -> Option<KeyCode> {
+    let code = match name.to_ascii_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "gt" => KeyCode::Char('>'),
+        "esc" => KeyCode::Esc,
+        "cr" | "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        f => return f.strip_prefix('f')?.parse().ok().map(KeyCode::F),
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(code: KeyCode, modifiers: KeyModifiers) -> KeyChord {
+        KeyChord { code, modifiers }
+    }
+
+    fn found(keymap: &Keymap, keys: &str) -> Option<Command> {
+        match keymap.lookup(&parse_sequence(keys).unwrap()) {
+            Lookup::Found(Binding::Command(command)) => Some(command),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_chords() {
+        let parse = |text| KeyChord::parse(text).unwrap();
+        assert_eq!(parse("x"), chord(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(parse("G"), chord(KeyCode::Char('G'), KeyModifiers::NONE));
+        assert_eq!(
+            parse("<C-r>"),
+            chord(KeyCode::Char('r'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse("<S-g>"),
+            chord(KeyCode::Char('G'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("<C-->"),
+            chord(KeyCode::Char('-'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse("<c-a-Space>"),
+            chord(
+                KeyCode::Char(' '),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            )
+        );
+        assert_eq!(parse("<F5>"), chord(KeyCode::F(5), KeyModifiers::NONE));
+        assert_eq!(parse("<CR>"), chord(KeyCode::Enter, KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn rejects_invalid_chords() {
+        for text in ["", "ab", "<>", "<X-a>", "<nope>", "<C-a"] {
+            assert!(KeyChord::parse(text).is_err(), "{text} was accepted");
+        }
+        assert!(parse_sequence("").is_err());
+    }
+
+    #[test]
+    fn parses_sequences() {
+        assert_eq!(
+            parse_sequence("<C-w>j").unwrap(),
+            vec![
+                chord(KeyCode::Char('w'), KeyModifiers::CONTROL),
+                chord(KeyCode::Char('j'), KeyModifiers::NONE),
+            ]
+        );
+        // a lone '<' is just the key
+        assert_eq!(parse_sequence("<").unwrap().len(), 1);
+        assert_eq!(parse_sequence("gg").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn chords_print_the_way_they_parse() {
+        for text in ["x", "<C-r>", "<Space>", "<CR>", "<lt>", "<A-F2>"] {
+            assert_eq!(KeyChord::parse(text).unwrap().to_string(), text);
+        }
+    }
+
+    #[test]
+    fn user_keymap_overrides_the_defaults() {
+        let mut keymap = Keymap::default();
+        let errors = keymap.load(
+            "[normal]\n\"J\" = \"move_down\"\n\"j\" = \"none\"\n\"gt\" = \"go_top\"\n",
+            "test",
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+
+        assert_eq!(found(&keymap, "J"), Some(Command::MoveDown));
+        assert_eq!(found(&keymap, "j"), None);
+        assert_eq!(found(&keymap, "gt"), Some(Command::GoTop));
+        assert_eq!(found(&keymap, "gg"), Some(Command::GoTop));
+        assert!(matches!(
+            keymap.lookup(&parse_sequence("g").unwrap()),
+            Lookup::Pending
+        ));
+    }
+
+    #[test]
+    fn reports_bad_entries() {
+        let mut keymap = Keymap::default();
+        let errors = keymap.load("[normal]\n\"<X-a>\" = \"quit\"\n\"x\" = \"nope\"\n", "test");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(keymap.load("[insert]\n", "test").len(), 1);
+    }
+}
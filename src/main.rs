@@ -5,20 +5,45 @@ use ratatui::{backend::CrosstermBackend, layout::Rect, Terminal};
 
 use crate::{
     app::{App, AppResult},
+    config::Config,
     event::{Event, EventHandler},
     tui::Tui,
 };
 
 pub mod app;
+pub mod archive;
+pub mod buffer;
+pub mod command;
+pub mod config;
+pub mod details;
 pub mod directory_entry;
+pub mod du;
 pub mod event;
 pub mod file_entry;
 pub mod filesystem_entry;
+pub mod finder;
+pub mod fs_ops;
+pub mod git;
+pub mod grep;
 pub mod handler;
+pub mod hexdump;
+pub mod highlight;
 pub mod input;
+pub mod jobs;
+pub mod journal;
+pub mod keymap;
+pub mod pdf;
+pub mod preview;
+pub mod register;
+pub mod search;
 pub mod sorter;
+pub mod thumbnail;
+pub mod trash;
 pub mod tui;
 pub mod ui;
+pub mod usage;
+pub mod visibility;
+pub mod watcher;
 
 #[tokio::main]
 async fn main() -> AppResult<()> {
@@ -26,17 +51,24 @@ async fn main() -> AppResult<()> {
     let backend = CrosstermBackend::new(io::stdout());
     let terminal = Terminal::new(backend)?;
     let size = terminal.size();
-    let events = EventHandler::new(250);
+    let config = Config::load();
+    let events = EventHandler::new(config.options().tick_rate);
+    let sender = events.sender();
     let mut keys = KeyHandler::new();
     let mut tui = Tui::new(terminal, events);
     tui.init()?;
 
     // Create an application.
-    let mut app = App::new(size.unwrap());
+    let mut app = App::new(size.unwrap(), sender, config);
+    keys.run_startup_requests(&mut app);
 
     // Start the main loop.
     while app.running {
         // Render the user interface.
+        app.watch_dirs();
+        app.follow_git();
+        app.follow_sizes();
+        app.request_preview();
         tui.draw(&mut app)?;
         // Handle events.
 
@@ -47,6 +79,15 @@ async fn main() -> AppResult<()> {
             Event::Resize(width, height) => {
                 app.generate_layout(Rect::new(0, 0, width, height));
             }
+            Event::Job(job_event) => app.handle_job_event(job_event),
+            Event::Finder(finder_event) => app.handle_finder_event(finder_event),
+            Event::Grep(grep_event) => app.handle_grep_event(grep_event),
+            Event::PreviewReady(preview) => app.handle_preview_ready(preview),
+            Event::FsChanged(paths) => app.handle_fs_changed(paths),
+            Event::GitStatus(status) => app.git.handle_ready(status),
+            Event::DirSize(size) => app.handle_dir_size(size),
+            Event::Usage(usage_event) => app.handle_usage_event(usage_event),
+            Event::ArchiveOpened(path, archive) => app.handle_archive_opened(path, archive),
         }
     }
 
@@ -0,0 +1,132 @@
+// PDFs for the preview. Parsing the document is the slow part, so it's kept
+// around and pages are only extracted once they're looked at
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use lopdf::{decode_text_string, Document};
+
+/// Parsed documents kept around, they can get big.
+const CACHE_SIZE: usize = 8;
+
+pub fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+}
+
+#[derive(Debug)]
+pub struct Pdf {
+    document: Document,
+    modified: SystemTime,
+    pub pages: u32,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub producer: Option<String>,
+    /// The one being shown, starting at 1.
+    pub page: u32,
+    texts: HashMap<u32, String>,
+}
+
+impl Pdf {
+    fn load(path: &Path, modified: SystemTime) -> Result<Self, String> {
+        let document = Document::load(path).map_err(|e| e.to_string())?;
+        let pages = document.get_pages().len() as u32;
+        let info = |key: &[u8]| {
+            let info = document.trailer.get(b"Info").ok()?;
+            let (_, info) = document.dereference(info).ok()?;
+            let value = info.as_dict().ok()?.get(key).ok()?;
+            decode_text_string(value)
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+
+        Ok(Self {
+            title: info(b"Title"),
+            author: info(b"Author"),
+            producer: info(b"Producer"),
+            document,
+            modified,
+            pages,
+            page: 1,
+            texts: HashMap::new(),
+        })
+    }
+
+    /// Text of the current page, extracted the first time it's asked for.
+    pub fn text(&mut self) -> &str {
+        let (document, page) = (&self.document, self.page);
+        self.texts.entry(page).or_insert_with(|| {
+            document
+                .extract_text(&[page])
+                .unwrap_or("Failed to parse PDF".to_string())
+        })
+    }
+
+    fn turn_page(&mut self, forward: bool) {
+        self.page = if forward {
+            (self.page + 1).min(self.pages.max(1))
+        } else {
+            (self.page - 1).max(1)
+        };
+    }
+}
+
+/// Shared by the preview tasks, like the thumbnails.
+#[derive(Debug, Default)]
+pub struct PdfCache {
+    pdfs: Mutex<HashMap<PathBuf, Pdf>>,
+    /// Oldest first, for evicting.
+    order: Mutex<VecDeque<PathBuf>>,
+}
+
+impl PdfCache {
+    /// Runs `f` on the document at `path`, parsing it again only if the file
+    /// changed since.
+    pub fn with<F, R>(&self, path: &Path, f: F) -> Result<R, String>
+    where
+        F: FnOnce(&mut Pdf) -> R,
+    {
+        let modified = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .map_err(|e| e.to_string())?;
+
+        {
+            let mut pdfs = self.pdfs.lock().unwrap();
+            if let Some(pdf) = pdfs.get_mut(path).filter(|pdf| pdf.modified == modified) {
+                return Ok(f(pdf));
+            }
+        }
+
+        // not holding the lock while parsing, turning pages shouldn't wait
+        let pdf = Pdf::load(path, modified)?;
+        let mut pdfs = self.pdfs.lock().unwrap();
+        let mut order = self.order.lock().unwrap();
+        order.retain(|p| p != path);
+        order.push_back(path.to_path_buf());
+        while order.len() > CACHE_SIZE {
+            if let Some(oldest) = order.pop_front() {
+                pdfs.remove(&oldest);
+            }
+        }
+        pdfs.insert(path.to_path_buf(), pdf);
+        Ok(f(pdfs.get_mut(path).expect("just inserted")))
+    }
+
+    /// Moves a document a page forward or back, if it was previewed before.
+    pub fn turn_page(&self, path: &Path, forward: bool) -> bool {
+        match self.pdfs.lock().unwrap().get_mut(path) {
+            Some(pdf) => {
+                pdf.turn_page(forward);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,576 @@
+// previews are made on the blocking pool so a huge file or a slow disk never
+// holds up the UI. Only the latest request matters, moving the cursor cancels
+// whatever was still being worked on and the result comes back as an event
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use flate2::read::MultiGzDecoder;
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    archive::{self, Archive},
+    event::Event,
+    filesystem_entry::FileSystemEntry,
+    hexdump,
+    highlight::Highlighter,
+    pdf::{self, Pdf, PdfCache},
+    sorter::Sorter,
+    thumbnail::{self, ThumbnailCache},
+    ui::human_size,
+    visibility::Visibility,
+};
+
+/// Finished previews kept around, about a directory's worth of files.
+const CACHE_SIZE: usize = 64;
+/// Longer lines are cut, nobody scrolls sideways through a preview.
+const LINE_LIMIT: usize = 1024;
+/// How far into a line that got cut to look for the next one.
+const SKIP_LIMIT: u64 = 1024 * 1024;
+/// Lines are only counted this far in, multi-GB logs would take a while.
+const COUNT_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Whatever stat said when the request was made, so a changed file isn't
+/// served from the cache. The line is set for the lines around a grep hit.
+type Key = (PathBuf, Option<SystemTime>, Option<usize>);
+
+/// What a background task is asked to look at.
+#[derive(Debug, Clone)]
+enum Subject {
+    /// A file or a directory on disk.
+    Path,
+    /// Something inside an archive, read from the archive itself.
+    Member(Arc<Archive>),
+    /// The lines of a file around a line, with that one marked.
+    Around(usize),
+}
+
+#[derive(Debug)]
+pub enum Content {
+    /// Everything in a directory, sorted. Drawn like the other panes, so
+    /// marks and the cut register show up.
+    Dir(Vec<FileSystemEntry>),
+    Text(Text<'static>),
+}
+
+#[derive(Debug)]
+pub struct Preview {
+    key: Key,
+    size: (u16, u16),
+    sort: Sorter,
+    pub content: Content,
+}
+
+#[derive(Debug)]
+struct Request {
+    key: Key,
+    size: (u16, u16),
+    sort: Sorter,
+    cancel: Arc<AtomicBool>,
+}
+
+/// What the background tasks need, all of it cheap to clone.
+#[derive(Debug, Clone)]
+struct Tools {
+    highlighter: Arc<Highlighter>,
+    thumbnails: Arc<ThumbnailCache>,
+    pdfs: Arc<PdfCache>,
+    visibility: Arc<Visibility>,
+}
+
+#[derive(Debug)]
+pub struct Previewer {
+    sender: UnboundedSender<Event>,
+    tools: Tools,
+    /// The one the cursor is on, done or not.
+    wanted: Option<Request>,
+    previews: HashMap<Key, Preview>,
+    /// Oldest first, for evicting.
+    order: VecDeque<Key>,
+}
+
+impl Previewer {
+    pub fn new(sender: UnboundedSender<Event>) -> Self {
+        Self {
+            sender,
+            tools: Tools {
+                highlighter: Arc::new(Highlighter::default()),
+                thumbnails: Arc::default(),
+                pdfs: Arc::default(),
+                visibility: Arc::default(),
+            },
+            wanted: None,
+            previews: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn highlighter(&self) -> &Highlighter {
+        &self.tools.highlighter
+    }
+
+    /// Everything highlighted before has the old colours, so it's all dropped.
+    pub fn set_theme(&mut self, name: &str) -> Result<(), String> {
+        Arc::make_mut(&mut self.tools.highlighter).set_theme(name)?;
+        self.clear();
+        Ok(())
+    }
+
+    /// Directory previews listed before may show what's hidden now, or the
+    /// other way around.
+    pub fn set_visibility(&mut self, visibility: Arc<Visibility>) {
+        self.tools.visibility = visibility;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.cancel();
+        self.previews.clear();
+        self.order.clear();
+    }
+
+    /// Asks for a preview of `path` that fits in `size`, unless that's what's
+    /// already being shown or made.
+    pub fn request(&mut self, path: &Path, size: (u16, u16), sort: Sorter) {
+        self.start(path, Subject::Path, size, sort);
+    }
+
+    /// Like [`Self::request`] for a member of `archive`, getting to one in a
+    /// compressed tar means decompressing everything before it.
+    pub fn request_member(
+        &mut self,
+        archive: Arc<Archive>,
+        path: &Path,
+        size: (u16, u16),
+        sort: Sorter,
+    ) {
+        self.start(path, Subject::Member(archive), size, sort);
+    }
+
+    /// The lines of `path` around `line`, counting from 1.
+    pub fn request_around(&mut self, path: &Path, line: usize, size: (u16, u16)) {
+        self.start(path, Subject::Around(line), size, Sorter::default());
+    }
+
+    fn start(&mut self, path: &Path, subject: Subject, size: (u16, u16), sort: Sorter) {
+        let line = match subject {
+            Subject::Around(line) => Some(line),
+            _ => None,
+        };
+        let same =
+            |r: &Request| r.key.0 == path && r.key.2 == line && r.size == size && r.sort == sort;
+        if self.wanted.as_ref().is_some_and(same) {
+            return;
+        }
+        self.cancel();
+
+        // members aren't on disk, the archive doesn't change under its listing
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let key = (path.to_path_buf(), modified, line);
+        let request = Request {
+            key,
+            size,
+            sort,
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+
+        if self.ready(&request).is_none() {
+            let (key, cancel) = (request.key.clone(), request.cancel.clone());
+            let (sender, tools) = (self.sender.clone(), self.tools.clone());
+            tokio::task::spawn_blocking(move || {
+                let content = match subject {
+                    Subject::Path => generate(&key.0, size, sort, &tools, &cancel),
+                    Subject::Member(archive) => {
+                        Some(preview_member(&archive, &key.0, size, sort, &tools))
+                    }
+                    Subject::Around(line) => Some(preview_around(&key.0, line, size.1)),
+                };
+                let Some(content) = content else {
+                    return;
+                };
+                let preview = Preview {
+                    key,
+                    size,
+                    sort,
+                    content,
+                };
+                if !cancel.load(Ordering::Relaxed) {
+                    let _ = sender.send(Event::PreviewReady(preview));
+                }
+            });
+        }
+        self.wanted = Some(request);
+    }
+
+    /// Makes the next request look at the file again, it changed on disk.
+    pub fn refresh(&mut self) {
+        self.cancel();
+    }
+
+    /// Drops the request, if the cursor went somewhere that isn't previewed
+    /// this way.
+    pub fn cancel(&mut self) {
+        if let Some(request) = self.wanted.take() {
+            request.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Cancelled requests never make it here, and one that finished just
+    /// before the cursor moved is still right for its file.
+    pub fn handle_ready(&mut self, preview: Preview) {
+        if !self.previews.contains_key(&preview.key) {
+            self.order.push_back(preview.key.clone());
+        }
+        self.previews.insert(preview.key.clone(), preview);
+        while self.order.len() > CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.previews.remove(&oldest);
+            }
+        }
+    }
+
+    /// The preview of what the cursor is on, `None` while it's being made.
+    pub fn current(&self) -> Option<&Content> {
+        self.ready(self.wanted.as_ref()?).map(|p| &p.content)
+    }
+
+    /// Pages through a PDF shown in the preview.
+    pub fn turn_page(&mut self, path: &Path, forward: bool) -> bool {
+        if !self.tools.pdfs.turn_page(path, forward) {
+            return false;
+        }
+        self.order.retain(|(p, _, _)| p != path);
+        self.previews.retain(|(p, _, _), _| p != path);
+        self.wanted = None;
+        true
+    }
+
+    fn ready(&self, request: &Request) -> Option<&Preview> {
+        self.previews
+            .get(&request.key)
+            .filter(|p| p.size == request.size && p.sort == request.sort)
+    }
+}
+
+/// `None` if it got cancelled along the way.
+fn generate(
+    path: &Path,
+    (width, height): (u16, u16),
+    sort: Sorter,
+    tools: &Tools,
+    cancel: &AtomicBool,
+) -> Option<Content> {
+    if path.is_dir() {
+        return preview_dir(path, sort, &tools.visibility, cancel);
+    }
+    if !path.is_file() {
+        let text = Text::from("Unkwnown entry type");
+        return Some(Content::Text(
+            text.style(Style::default().fg(Color::Red).bg(Color::Black)),
+        ));
+    }
+
+    let text = if thumbnail::is_image(path) {
+        match tools.thumbnails.get(path, width, height) {
+            Ok(lines) => Text::from(lines),
+            Err(err) => Text::from(format!("Failed to decode image: {}", err))
+                .style(Style::default().fg(Color::Red)),
+        }
+    } else if pdf::is_pdf(path) {
+        match tools.pdfs.with(path, pdf_lines) {
+            Ok(lines) => Text::from(lines),
+            Err(err) => Text::from(err),
+        }
+    } else {
+        match preview_text(path, (width, height), tools, cancel) {
+            Ok(Some(text)) => text,
+            Ok(None) => return None,
+            Err(_) => Text::from("Failed to parse whatever that is")
+                .style(Style::default().fg(Color::White)),
+        }
+    };
+    Some(Content::Text(text))
+}
+
+fn preview_dir(
+    path: &Path,
+    sort: Sorter,
+    visibility: &Visibility,
+    cancel: &AtomicBool,
+) -> Option<Content> {
+    let dir = match fs::read_dir(path) {
+        Ok(dir) => dir,
+        Err(err) => {
+            let message = match err.kind() {
+                io::ErrorKind::PermissionDenied => "Permission denied!",
+                _ => "Unknown error",
+            };
+            return Some(Content::Text(Text::from(message)));
+        }
+    };
+
+    let mut entries = Vec::new();
+    for entry in dir.filter_map(|e| e.ok()) {
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+        entries.push(FileSystemEntry::new(entry.path()));
+    }
+    visibility.apply(path, &mut entries, true);
+    entries.sort_by(|a, b| sort.entries(a, b));
+    Some(Content::Dir(entries))
+}
+
+/// The children of a directory in there, or the start of a file shown as
+/// text or hex.
+fn preview_member(
+    archive: &Archive,
+    path: &Path,
+    (width, height): (u16, u16),
+    sort: Sorter,
+    tools: &Tools,
+) -> Content {
+    let inner = archive.inner(path).unwrap_or(Path::new(""));
+    if archive.is_dir(inner) {
+        let mut entries: Vec<_> = archive
+            .children(inner)
+            .into_iter()
+            .map(|(path, dir)| FileSystemEntry::member(path, dir))
+            .collect();
+        tools.visibility.apply(path, &mut entries, false);
+        entries.sort_by(|a, b| sort.entries(a, b));
+        return Content::Dir(entries);
+    }
+
+    let bytes = match archive.head(inner) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            let text = format!("Failed to read from the archive: {}", err);
+            return Content::Text(Text::from(text));
+        }
+    };
+    let height = usize::from(height);
+    // only the start was read, a char cut in half at the end is fine
+    let text = match std::str::from_utf8(&bytes) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+
+    Content::Text(match text.filter(|t| !t.contains('\0')) {
+        Some(text) => {
+            let lines: Vec<String> = text
+                .split_inclusive('\n')
+                .take(height)
+                .map(String::from)
+                .collect();
+            Text::from(tools.highlighter.highlight(inner, &lines))
+        }
+        None => Text::from(hexdump::dump_bytes(&bytes, width.into(), height)),
+    })
+}
+
+/// Numbered lines with `line` in the middle and marked.
+fn preview_around(path: &Path, line: usize, height: u16) -> Content {
+    let height = usize::from(height);
+    let first = line.saturating_sub(height / 2).max(1);
+    let lines: Vec<Line> = match File::open(path) {
+        Ok(file) => BufReader::new(file)
+            .lines()
+            .skip(first - 1)
+            .take(height)
+            .map_while(Result::ok)
+            .enumerate()
+            .map(|(i, text)| {
+                let number = first + i;
+                let text = format!("{:>5} {}", number, text.replace('\t', "    "));
+                let style = if number == line {
+                    Style::default().fg(Color::Black).bg(Color::LightYellow)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+                Line::styled(text, style)
+            })
+            .collect(),
+        Err(e) => vec![Line::styled(e.to_string(), Style::default().fg(Color::Red))],
+    };
+    Content::Text(Text::from(lines))
+}
+
+/// Whatever the document says about itself, then the current page.
+fn pdf_lines(pdf: &mut Pdf) -> Vec<Line<'static>> {
+    let label = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+
+    if let Some(title) = &pdf.title {
+        let bold = Style::default()
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD);
+        lines.push(Line::styled(title.clone(), bold));
+    }
+    for (name, value) in [("Author", &pdf.author), ("Producer", &pdf.producer)] {
+        if let Some(value) = value {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{}: ", name), label),
+                Span::styled(value.clone(), Style::default().fg(Color::White)),
+            ]));
+        }
+    }
+    lines.push(Line::styled(
+        format!("Page {}/{}  [ ] to turn", pdf.page, pdf.pages),
+        label,
+    ));
+    lines.push(Line::default());
+
+    let text = Style::default().fg(Color::White);
+    lines.extend(
+        pdf.text()
+            .lines()
+            .map(|line| Line::styled(line.to_string(), text)),
+    );
+    lines
+}
+
+/// Only what fits on screen is read, even if the file is gigabytes of one
+/// long line. Compressed files are shown decompressed. `None` if it got
+/// cancelled.
+fn preview_text(
+    path: &Path,
+    (width, height): (u16, u16),
+    tools: &Tools,
+    cancel: &AtomicBool,
+) -> io::Result<Option<Text<'static>>> {
+    let height = usize::from(height);
+    // one line goes to the header
+    let Some(lines) = read_head(&mut open(path)?, height.saturating_sub(1))? else {
+        // not utf-8 or with NULs in it, binary either way
+        return Ok(Some(Text::from(hexdump::dump(
+            open(path)?,
+            width.into(),
+            height,
+        )?)));
+    };
+
+    let (count, complete) = count_lines(open(path)?, cancel)?;
+    if cancel.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+
+    let mut header = human_size(fs::metadata(path)?.len());
+    let name = match compression(path) {
+        Some(format) => {
+            header += &format!(" {}", format);
+            // so `app.rs.gz` still gets highlighted as rust
+            path.with_extension("")
+        }
+        None => path.to_path_buf(),
+    };
+    header += &format!(" · {}{} lines", count, if complete { "" } else { "+" });
+
+    let mut text = vec![Line::styled(header, Style::default().fg(Color::DarkGray))];
+    text.extend(tools.highlighter.highlight(&name, &lines));
+    Ok(Some(Text::from(text)))
+}
+
+/// Compressed logs and the like, compressed tarballs are archives and get
+/// browsed instead.
+fn compression(path: &Path) -> Option<&'static str> {
+    if archive::Format::detect(path).is_some() {
+        return None;
+    }
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "gz" => Some("gzip"),
+        "zst" => Some("zstd"),
+        _ => None,
+    }
+}
+
+fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(match compression(path) {
+        Some("gzip") => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+        Some(_) => Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?)),
+        None => Box::new(file),
+    })
+}
+
+/// Up to `count` lines from the start, line endings included. Lines longer
+/// than [`LINE_LIMIT`] are cut short. `None` if it doesn't look like text.
+fn read_head(reader: &mut dyn BufRead, count: usize) -> io::Result<Option<Vec<String>>> {
+    let mut lines = Vec::new();
+    let mut skip_budget = SKIP_LIMIT;
+    let mut buf = Vec::new();
+    while lines.len() < count {
+        buf.clear();
+        let read = reader.take(LINE_LIMIT as u64).read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        if buf.contains(&0) {
+            return Ok(None);
+        }
+
+        let cut = read == LINE_LIMIT && !buf.ends_with(b"\n");
+        let line = match std::str::from_utf8(&buf) {
+            Ok(line) => line.to_string(),
+            // the limit went through the middle of a char
+            Err(e) if cut && e.error_len().is_none() => {
+                String::from_utf8_lossy(&buf[..e.valid_up_to()]).into_owned()
+            }
+            Err(_) => return Ok(None),
+        };
+        lines.push(line);
+
+        if cut {
+            // the rest of the line doesn't fit anyway, but a file that's one
+            // giant line isn't worth reading to the end either
+            let skipped = reader.take(skip_budget).skip_until(b'\n')? as u64;
+            if skipped >= skip_budget {
+                break;
+            }
+            skip_budget -= skipped;
+        }
+    }
+    Ok(Some(lines))
+}
+
+/// Lines in the first [`COUNT_LIMIT`] bytes, and whether that was all of it.
+fn count_lines(mut reader: impl Read, cancel: &AtomicBool) -> io::Result<(u64, bool)> {
+    let mut buf = vec![0; 64 * 1024];
+    let (mut lines, mut total) = (0, 0);
+    let mut last = b'\n';
+    let mut complete = true;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        lines += buf[..read].iter().filter(|&&b| b == b'\n').count() as u64;
+        last = buf[read - 1];
+        total += read as u64;
+        if total >= COUNT_LIMIT || cancel.load(Ordering::Relaxed) {
+            complete = false;
+            break;
+        }
+    }
+    // a last line without a newline still counts
+    if last != b'\n' {
+        lines += 1;
+    }
+    Ok((lines, complete))
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegisterKind {
+    #[default]
+    Copy,
+    Cut,
+}
+
+/// Entries yanked or cut from any number of directories, waiting to be
+/// pasted somewhere.
+#[derive(Debug, Default)]
+pub struct Register {
+    pub paths: Vec<PathBuf>,
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fill(&mut self, mut paths: Vec<PathBuf>, kind: RegisterKind) {
+        paths.sort();
+        paths.dedup();
+        self.paths = paths;
+        self.kind = kind;
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn contains(&self, path: &PathBuf) -> bool {
+        self.paths.contains(path)
+    }
+}
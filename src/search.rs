@@ -0,0 +1,38 @@
+use std::ops::Range;
+
+/// Byte range of the first occurrence of `pattern` in `haystack`. Smart
+/// case like vim: an all lowercase pattern ignores case.
+pub fn find(haystack: &str, pattern: &str) -> Option<Range<usize>> {
+    if pattern.is_empty() {
+        return None;
+    }
+
+    if pattern.chars().any(char::is_uppercase) {
+        return haystack
+            .find(pattern)
+            .map(|start| start..start + pattern.len());
+    }
+
+    // lowercasing can change byte lengths, so compare char by char and keep
+    // track of where we are in the original
+    let pattern: Vec<char> = pattern.chars().collect();
+    let chars: Vec<(usize, char)> = haystack.char_indices().collect();
+    (0..chars.len()).find_map(|start| {
+        let window = chars.get(start..start + pattern.len())?;
+        let matches = window
+            .iter()
+            .zip(&pattern)
+            .all(|((_, a), b)| a.to_lowercase().eq(b.to_lowercase()));
+
+        matches.then(|| {
+            let end = chars
+                .get(start + pattern.len())
+                .map_or(haystack.len(), |(i, _)| *i);
+            chars[start].0..end
+        })
+    })
+}
+
+pub fn matches(haystack: &str, pattern: &str) -> bool {
+    pattern.is_empty() || find(haystack, pattern).is_some()
+}
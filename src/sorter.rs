@@ -1,69 +1,216 @@
-use std::{cmp::Ordering, fs::DirEntry, path::PathBuf};
+use std::cmp::Ordering;
 
-pub enum Sorter {
+use crate::filesystem_entry::FileSystemEntry;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
     DirsFirst,
     FilesFirst,
     Alphabetical,
+    /// Biggest first, like `ls -S`.
+    Size,
+    /// Newest first, like `ls -t`.
+    Modified,
+    Changed,
+    Extension,
+    /// `file2` before `file10`.
+    Natural,
+}
+
+/// Every key with its name and what picks it in the sort menu.
+pub const KEYS: &[(SortKey, &str, char)] = &[
+    (SortKey::DirsFirst, "dirs_first", 'd'),
+    (SortKey::FilesFirst, "files_first", 'f'),
+    (SortKey::Alphabetical, "alphabetical", 'a'),
+    (SortKey::Size, "size", 's'),
+    (SortKey::Modified, "mtime", 'm'),
+    (SortKey::Changed, "ctime", 'c'),
+    (SortKey::Extension, "extension", 'e'),
+    (SortKey::Natural, "natural", 'n'),
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Sorter {
+    pub key: SortKey,
+    /// Flips the order inside the groups, directories still come first.
+    pub reverse: bool,
 }
 
 impl Sorter {
-    pub fn paths(&self, a: &PathBuf, b: &PathBuf) -> Ordering {
-        match self {
-            Sorter::DirsFirst => match (a.is_dir(), b.is_dir()) {
-                // using just cmp on file_names compares lexographically
-                // meaning that uppercase files would come before lowercase
-                (true, true) | (false, false) => {
-                    a.file_name()
-                        .unwrap()
-                        .to_string_lossy()
-                        .to_lowercase()
-                        .cmp(&b.file_name()
-                            .unwrap()
-                            .to_string_lossy()
-                            .to_lowercase())
-                }
-                (true, false) => Ordering::Less,
-                (false, true) => Ordering::Greater,
-            },
-            Sorter::FilesFirst => match (a.is_dir(), b.is_dir()) {
-                (true, true) | (false, false) => a.file_name().cmp(&b.file_name()),
-                (true, false) => Ordering::Greater,
-                (false, true) => Ordering::Less,
-            },
-            Sorter::Alphabetical => a.file_name().cmp(&b.file_name()),
-        }
+    pub fn from_name(name: &str) -> Option<Self> {
+        KEYS.iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(key, _, _)| Sorter {
+                key: *key,
+                reverse: false,
+            })
+    }
+
+    pub fn name(&self) -> &'static str {
+        KEYS.iter()
+            .find(|(key, _, _)| *key == self.key)
+            .map_or("dirs_first", |(_, name, _)| name)
+    }
+
+    pub fn entries(&self, a: &FileSystemEntry, b: &FileSystemEntry) -> Ordering {
+        let group = match self.key {
+            SortKey::Alphabetical => Ordering::Equal,
+            SortKey::FilesFirst => a.is_dir().cmp(&b.is_dir()),
+            _ => b.is_dir().cmp(&a.is_dir()),
+        };
+
+        let order = match self.key {
+            // using just cmp on file_names compares lexographically
+            // meaning that uppercase files would come before lowercase
+            SortKey::DirsFirst => lowercase(a).cmp(&lowercase(b)),
+            SortKey::FilesFirst | SortKey::Alphabetical => a.name().cmp(b.name()),
+            // directories are only compared once they've been sized
+            SortKey::Size => b
+                .total_size()
+                .cmp(&a.total_size())
+                .then_with(|| lowercase(a).cmp(&lowercase(b))),
+            SortKey::Modified => b
+                .modified
+                .cmp(&a.modified)
+                .then_with(|| a.name().cmp(b.name())),
+            SortKey::Changed => b
+                .changed
+                .cmp(&a.changed)
+                .then_with(|| a.name().cmp(b.name())),
+            SortKey::Extension => extension(a)
+                .cmp(&extension(b))
+                .then_with(|| lowercase(a).cmp(&lowercase(b))),
+            SortKey::Natural => natord::compare_ignore_case(
+                &a.name().to_string_lossy(),
+                &b.name().to_string_lossy(),
+            ),
+        };
+
+        let order = if self.reverse { order.reverse() } else { order };
+        group.then(order)
+    }
+}
+
+fn lowercase(entry: &FileSystemEntry) -> String {
+    entry.name().to_string_lossy().to_lowercase()
+}
+
+/// Directories and files without one sort before everything else.
+fn extension(entry: &FileSystemEntry) -> Option<String> {
+    if entry.is_dir() {
+        None
+    } else {
+        entry
+            .path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+
+    fn entry(name: &str, dir: bool, size: u64, age: u64) -> FileSystemEntry {
+        let mut entry = FileSystemEntry::member(PathBuf::from("/x").join(name), dir);
+        entry.size = size;
+        entry.dir_size = dir.then_some(size);
+        entry.modified = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - age));
+        entry
+    }
+
+    fn sorted(key: SortKey, reverse: bool) -> Vec<String> {
+        let mut entries = [
+            entry("b.txt", false, 30, 2),
+            entry("Docs", true, 100, 5),
+            entry("file10", false, 10, 1),
+            entry("a.RS", false, 20, 4),
+            entry("file2", false, 40, 3),
+            entry("bin", true, 50, 0),
+        ];
+        let sorter = Sorter { key, reverse };
+        entries.sort_by(|a, b| sorter.entries(a, b));
+        entries
+            .iter()
+            .map(|e| e.name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn dirs_first_ignores_case() {
+        assert_eq!(
+            sorted(SortKey::DirsFirst, false),
+            ["bin", "Docs", "a.RS", "b.txt", "file10", "file2"]
+        );
+    }
+
+    #[test]
+    fn reverse_keeps_the_groups() {
+        assert_eq!(
+            sorted(SortKey::DirsFirst, true),
+            ["Docs", "bin", "file2", "file10", "b.txt", "a.RS"]
+        );
+        assert_eq!(
+            sorted(SortKey::FilesFirst, true),
+            ["file2", "file10", "b.txt", "a.RS", "bin", "Docs"]
+        );
+    }
+
+    #[test]
+    fn alphabetical_mixes_dirs_and_files() {
+        assert_eq!(
+            sorted(SortKey::Alphabetical, false),
+            ["Docs", "a.RS", "b.txt", "bin", "file10", "file2"]
+        );
+    }
+
+    #[test]
+    fn size_and_time_put_the_biggest_and_newest_first() {
+        assert_eq!(
+            sorted(SortKey::Size, false),
+            ["Docs", "bin", "file2", "b.txt", "a.RS", "file10"]
+        );
+        assert_eq!(
+            sorted(SortKey::Modified, false),
+            ["bin", "Docs", "file10", "b.txt", "file2", "a.RS"]
+        );
+    }
+
+    #[test]
+    fn extension_and_natural() {
+        assert_eq!(
+            sorted(SortKey::Extension, false),
+            ["bin", "Docs", "file10", "file2", "a.RS", "b.txt"]
+        );
+        assert_eq!(
+            sorted(SortKey::Natural, false),
+            ["bin", "Docs", "a.RS", "b.txt", "file2", "file10"]
+        );
+    }
+
+    #[test]
+    fn unsized_dirs_sort_after_sized_ones() {
+        let sorter = Sorter {
+            key: SortKey::Size,
+            reverse: false,
+        };
+        let mut unsized_dir = entry("a", true, 0, 0);
+        unsized_dir.dir_size = None;
+        let sized = entry("b", true, 1, 0);
+        assert_eq!(sorter.entries(&sized, &unsized_dir), Ordering::Less);
     }
 
-    pub fn entries(&self, a: &DirEntry, b: &DirEntry) -> Ordering {
-        match self {
-            Sorter::DirsFirst => {
-                let a_is_dir = a.path().is_dir();
-                let b_is_dir = b.path().is_dir();
-
-                match (a_is_dir, b_is_dir) {
-                (true, true) | (false, false) => {
-                    a.file_name()
-                        .to_string_lossy()
-                        .to_lowercase()
-                        .cmp(&b.file_name()
-                            .to_string_lossy()
-                            .to_lowercase())
-                }
-                    (true, false) => Ordering::Less,
-                    (false, true) => Ordering::Greater,
-                }
-            }
-            Sorter::FilesFirst => {
-                let a_is_dir = a.path().is_dir();
-                let b_is_dir = b.path().is_dir();
-
-                match (a_is_dir, b_is_dir) {
-                    (true, true) | (false, false) => a.file_name().cmp(&b.file_name()),
-                    (true, false) => Ordering::Greater,
-                    (false, true) => Ordering::Less,
-                }
-            }
-            Sorter::Alphabetical => a.file_name().cmp(&b.file_name()),
+    #[test]
+    fn names_round_trip() {
+        for (key, name, _) in KEYS {
+            assert_eq!(Sorter::from_name(name).map(|s| s.key), Some(*key));
         }
+        assert_eq!(Sorter::from_name("nope"), None);
     }
 }
@@ -0,0 +1,127 @@
+// images drawn with half blocks: every cell is two pixels stacked, the top one
+// as the foreground of '▀' and the bottom one as the background
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use image::{imageops::FilterType, DynamicImage, ImageReader, Rgba};
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+
+/// Decoded thumbnails kept around, enough for going back and forth through a
+/// directory of photos.
+const CACHE_SIZE: usize = 64;
+
+pub const EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXTENSIONS.iter().any(|ext| e.eq_ignore_ascii_case(ext)))
+}
+
+#[derive(Debug)]
+struct Thumbnail {
+    /// Cells it was scaled to fit in, a resized pane means decoding again.
+    size: (u16, u16),
+    lines: Vec<Line<'static>>,
+}
+
+/// Shared by the preview tasks, which decode outside of the locks.
+#[derive(Debug, Default)]
+pub struct ThumbnailCache {
+    thumbnails: Mutex<HashMap<(PathBuf, SystemTime), Thumbnail>>,
+    /// Oldest first, for evicting.
+    order: Mutex<VecDeque<(PathBuf, SystemTime)>>,
+}
+
+impl ThumbnailCache {
+    /// The image at `path` scaled to fit `width` x `height` cells, decoded
+    /// only if it changed since the last time.
+    pub fn get(&self, path: &Path, width: u16, height: u16) -> Result<Vec<Line<'static>>, String> {
+        let mtime = path
+            .metadata()
+            .and_then(|m| m.modified())
+            .map_err(|e| e.to_string())?;
+        let key = (path.to_path_buf(), mtime);
+
+        if let Some(thumbnail) = self.thumbnails.lock().unwrap().get(&key) {
+            if thumbnail.size == (width, height) {
+                return Ok(thumbnail.lines.clone());
+            }
+        }
+
+        let image = ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| e.to_string())?
+            .decode()
+            .map_err(|e| e.to_string())?;
+        let lines = render(&image, width, height);
+
+        let mut thumbnails = self.thumbnails.lock().unwrap();
+        let mut order = self.order.lock().unwrap();
+        if !thumbnails.contains_key(&key) {
+            order.push_back(key.clone());
+        }
+        while order.len() > CACHE_SIZE {
+            if let Some(oldest) = order.pop_front() {
+                thumbnails.remove(&oldest);
+            }
+        }
+        thumbnails.insert(
+            key,
+            Thumbnail {
+                size: (width, height),
+                lines: lines.clone(),
+            },
+        );
+
+        Ok(lines)
+    }
+}
+
+fn render(image: &DynamicImage, width: u16, height: u16) -> Vec<Line<'static>> {
+    // keeps the aspect ratio, cells being about twice as tall as wide works
+    // out nicely with two pixels per cell
+    let pixels = image
+        .resize(width.into(), u32::from(height) * 2, FilterType::Triangle)
+        .to_rgba8();
+
+    (0..pixels.height())
+        .step_by(2)
+        .map(|y| {
+            let spans: Vec<_> = (0..pixels.width())
+                .map(|x| {
+                    let top = color(pixels.get_pixel(x, y));
+                    let bottom = if y + 1 < pixels.height() {
+                        color(pixels.get_pixel(x, y + 1))
+                    } else {
+                        Color::Reset
+                    };
+                    match (top, bottom) {
+                        (Color::Reset, Color::Reset) => Span::raw(" "),
+                        (Color::Reset, _) => Span::styled("▄", Style::default().fg(bottom)),
+                        _ => Span::styled("▀", Style::default().fg(top).bg(bottom)),
+                    }
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Mostly see-through pixels show the terminal's background.
+fn color(pixel: &Rgba<u8>) -> Color {
+    let [r, g, b, a] = pixel.0;
+    if a < 128 {
+        Color::Reset
+    } else {
+        Color::Rgb(r, g, b)
+    }
+}
@@ -0,0 +1,334 @@
+// FreeDesktop.org trash, see
+// https://specifications.freedesktop.org/trash-spec/trashspec-latest.html
+//
+// only the home trash ($XDG_DATA_HOME/Trash) is used, entries on other
+// filesystems get copied over by fs_ops::move_path
+
+use std::{
+    env,
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::ffi::OsStringExt,
+    path::{Path, PathBuf},
+};
+
+use chrono::{Local, NaiveDateTime};
+
+use crate::fs_ops;
+
+const INFO_EXT: &str = "trashinfo";
+const DATE_FMT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone)]
+pub struct Trash {
+    files: PathBuf,
+    info: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    /// Name of the entry inside `Trash/files`.
+    pub name: OsString,
+    /// Where the entry lived before being trashed.
+    pub original: PathBuf,
+    pub deleted: Option<NaiveDateTime>,
+    /// Location of the entry inside `Trash/files`.
+    pub trashed: PathBuf,
+    info_file: PathBuf,
+}
+
+impl Trash {
+    /// Locates the home trash, `None` if neither `$XDG_DATA_HOME` nor
+    /// `$HOME` are set.
+    pub fn new() -> Option<Self> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".local/share")))?;
+
+        let root = data_home.join("Trash");
+        Some(Self {
+            files: root.join("files"),
+            info: root.join("info"),
+        })
+    }
+
+    /// Moves `path` into the trash and writes its `.trashinfo` record.
+    pub fn put(&self, path: &Path) -> io::Result<TrashEntry> {
+        fs::create_dir_all(&self.files)?;
+        fs::create_dir_all(&self.info)?;
+
+        let original = std::path::absolute(path)?;
+        let base = original
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "can't trash the root"))?
+            .to_os_string();
+
+        // the info file is created first (and exclusively) to claim the name
+        let deleted = Local::now().naive_local();
+        let (name, info_file, mut info) = self.reserve(&base)?;
+        let record = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            encode(&original),
+            deleted.format(DATE_FMT)
+        );
+
+        let trashed = self.files.join(&name);
+        let res = info
+            .write_all(record.as_bytes())
+            .and_then(|_| fs_ops::move_path(&original, &trashed));
+
+        if let Err(e) = res {
+            let _ = fs::remove_file(&info_file);
+            return Err(e);
+        }
+
+        Ok(TrashEntry {
+            name,
+            original,
+            deleted: Some(deleted),
+            trashed,
+            info_file,
+        })
+    }
+
+    fn reserve(&self, base: &OsString) -> io::Result<(OsString, PathBuf, fs::File)> {
+        for n in 1.. {
+            let mut name = base.clone();
+            if n > 1 {
+                name.push(format!(".{}", n));
+            }
+
+            if fs_ops::occupied(&self.files.join(&name)) {
+                continue;
+            }
+
+            let mut info_name = name.clone();
+            info_name.push(".");
+            info_name.push(INFO_EXT);
+            let info_file = self.info.join(info_name);
+
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_file)
+            {
+                Ok(file) => return Ok((name, info_file, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!()
+    }
+
+    /// Lists everything in the trash, most recently deleted first.
+    pub fn list(&self) -> io::Result<Vec<TrashEntry>> {
+        let dir = match fs::read_dir(&self.info) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries: Vec<TrashEntry> = dir
+            .filter_map(|e| e.ok())
+            .filter_map(|e| self.read_info(&e.path()))
+            .collect();
+
+        entries.sort_by_key(|e| std::cmp::Reverse(e.deleted));
+        Ok(entries)
+    }
+
+    fn read_info(&self, info_file: &Path) -> Option<TrashEntry> {
+        if info_file.extension()? != INFO_EXT {
+            return None;
+        }
+
+        let contents = fs::read_to_string(info_file).ok()?;
+        let mut original = None;
+        let mut deleted = None;
+        for line in contents.lines().skip_while(|l| l.trim() != "[Trash Info]") {
+            if let Some(p) = line.strip_prefix("Path=") {
+                original = Some(decode(p.trim()));
+            } else if let Some(d) = line.strip_prefix("DeletionDate=") {
+                deleted = NaiveDateTime::parse_from_str(d.trim(), DATE_FMT).ok();
+            }
+        }
+
+        let name = info_file.file_stem()?.to_os_string();
+        Some(TrashEntry {
+            trashed: self.files.join(&name),
+            name,
+            original: original?,
+            deleted,
+            info_file: info_file.to_path_buf(),
+        })
+    }
+
+    /// Moves a trashed entry back to where it came from.
+    pub fn restore(&self, entry: &TrashEntry) -> io::Result<()> {
+        if fs_ops::occupied(&entry.original) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", entry.original.display()),
+            ));
+        }
+
+        if let Some(parent) = entry.original.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs_ops::move_path(&entry.trashed, &entry.original)?;
+        fs::remove_file(&entry.info_file)
+    }
+
+    /// Deletes a trashed entry for good.
+    pub fn purge(&self, entry: &TrashEntry) -> io::Result<()> {
+        match fs_ops::remove_path(&entry.trashed) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        fs::remove_file(&entry.info_file)
+    }
+}
+
+/// Cursor over the trash contents, shown in place of the explorer panes.
+#[derive(Debug, Default)]
+pub struct TrashView {
+    pub entries: Vec<TrashEntry>,
+    pub idx: usize,
+}
+
+impl TrashView {
+    pub fn new(entries: Vec<TrashEntry>) -> Self {
+        Self { entries, idx: 0 }
+    }
+
+    pub fn selected(&self) -> Option<&TrashEntry> {
+        self.entries.get(self.idx)
+    }
+
+    pub fn move_up(&mut self, wrap: bool) {
+        if self.idx > 0 {
+            self.idx -= 1;
+        } else if wrap {
+            self.idx = self.entries.len().saturating_sub(1);
+        }
+    }
+
+    pub fn move_down(&mut self, wrap: bool) {
+        if self.idx + 1 < self.entries.len() {
+            self.idx += 1;
+        } else if wrap {
+            self.idx = 0;
+        }
+    }
+
+    /// Drops the entry under the cursor after it was restored or purged.
+    pub fn remove_selected(&mut self) {
+        if self.idx < self.entries.len() {
+            self.entries.remove(self.idx);
+        }
+        self.idx = self.idx.min(self.entries.len().saturating_sub(1));
+    }
+}
+
+// the spec wants Path= percent-encoded like a URL path
+fn encode(path: &Path) -> String {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn decode(encoded: &str) -> PathBuf {
+    let bytes = encoded.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    // paths don't have to be utf-8, the bytes are what they were
+    PathBuf::from(OsString::from_vec(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_round_trips() {
+        for path in [
+            "/plain/path",
+            "/with space/and%percent",
+            "/ünï/cödé",
+            "/a+b&c=d?",
+        ] {
+            let encoded = encode(Path::new(path));
+            assert!(encoded.is_ascii(), "{encoded}");
+            assert_eq!(decode(&encoded), Path::new(path));
+        }
+        assert_eq!(encode(Path::new("/a b/c%")), "/a%20b/c%25");
+    }
+
+    #[test]
+    fn non_utf8_names_round_trip() {
+        let path = PathBuf::from(OsString::from_vec(b"/tmp/\xff\xfe name".to_vec()));
+        assert_eq!(decode(&encode(&path)), path);
+    }
+
+    #[test]
+    fn stray_percents_are_kept() {
+        assert_eq!(decode("/100%"), Path::new("/100%"));
+        assert_eq!(decode("/%zz%4"), Path::new("/%zz%4"));
+    }
+
+    #[test]
+    fn put_then_restore() {
+        let root = env::temp_dir().join(format!("fear-trash-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let trash = Trash {
+            files: root.join("Trash/files"),
+            info: root.join("Trash/info"),
+        };
+        let file = root.join("some file");
+        fs::write(&file, "x").unwrap();
+
+        let entry = trash.put(&file).unwrap();
+        assert!(!file.exists() && entry.trashed.exists());
+
+        let listed = trash.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].original, file);
+        assert_eq!(listed[0].name, "some file");
+
+        trash.restore(&listed[0]).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "x");
+        assert!(trash.list().unwrap().is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -51,6 +51,10 @@ impl<B: Backend> Tui<B> {
     /// [`Draw`]: ratatui::Terminal::draw
     /// [`rendering`]: crate::ui::render
     pub fn draw(&mut self, app: &mut App) -> AppResult<()> {
+        if app.needs_redraw {
+            self.terminal.clear()?;
+            app.needs_redraw = false;
+        }
         self.terminal.draw(|frame| ui::render(app, frame))?;
         Ok(())
     }
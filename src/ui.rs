@@ -1,23 +1,29 @@
-use std::{
-    fs, io,
-    path::{Path, PathBuf},
-};
-
-use lopdf::Document;
 use ratatui::{
-    layout::{Alignment, Position},
-    style::{Color, Style},
-    text::{Line, Text},
-    widgets::{
-        Block, Borders, Padding, Paragraph,
-    },
+    layout::{Alignment, Position, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{block::Title, Block, Borders, Clear, Padding, Paragraph, Wrap},
     Frame,
 };
 
-use crate::{app::App, sorter::Sorter};
+use crate::{
+    app::App,
+    buffer::{BufferMode, Change, DirBuffer},
+    details::Details,
+    filesystem_entry::FileSystemEntry,
+    finder::Finder,
+    git::GitStatus,
+    grep::Grep,
+    jobs::Job,
+    preview::Content,
+    register::RegisterKind,
+    search, sorter,
+    usage::{Node, Usage},
+};
 
-const SORTER: Sorter = Sorter::DirsFirst;
 const MARK: &str = "  ";
+/// Width of the bars in the disk usage view.
+const BAR: usize = 10;
 
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame) {
@@ -29,16 +35,59 @@ pub fn render(app: &mut App, frame: &mut Frame) {
 
     render_info_bar(frame, app);
 
-    match &app.parent_dir {
-        Some(_) => render_parent_pane(frame, app),
-        None => {}
+    if app.trash_view.is_some() {
+        render_trash_view(frame, app);
+    } else if let Some(grep) = &app.grep {
+        render_grep_results(frame, app, grep);
+        render_grep_preview(frame, app, grep);
+    } else if let Some(usage) = &app.usage {
+        render_usage(frame, app, usage);
+    } else {
+        match &app.parent_dir {
+            Some(_) => render_parent_pane(frame, app),
+            None => {}
+        }
+
+        match &app.buffer {
+            Some(buffer) => {
+                render_buffer_pane(frame, app, buffer);
+                match &buffer.plan {
+                    Some(plan) => render_plan(frame, app, buffer, plan),
+                    None => render_preview_pane(frame, app),
+                }
+            }
+            None => {
+                render_focus_pane(frame, app);
+                match app.jobs_view {
+                    Some(selected) => render_jobs_view(frame, app, selected),
+                    None => render_preview_pane(frame, app),
+                }
+            }
+        }
     }
 
-    render_focus_pane(frame, app);
+    if let Some(finder) = &app.finder {
+        render_finder(frame, finder);
+    }
 
-    render_preview_pane(frame, app);
+    if app.sort_menu {
+        render_sort_menu(frame, app);
+    }
 
-    if let Some(m) = &app.message {
+    if let Some(buffer) = app.buffer.as_ref().filter(|_| app.message.is_none()) {
+        render_buffer_status(frame, app, buffer);
+    } else if let Some(job) = app.jobs.jobs.first().filter(|_| app.message.is_none()) {
+        let more = match app.jobs.jobs.len() {
+            1 => String::new(),
+            n => format!(" (+{} more)", n - 1),
+        };
+        frame.render_widget(
+            Paragraph::new(Text::from(format_job(job) + &more))
+                .block(Block::default().borders(Borders::TOP))
+                .style(Style::default().fg(Color::Cyan)),
+            app.message_layout,
+        );
+    } else if let Some(m) = &app.message {
         match &app.input {
             Some(input) => {
                 frame.render_widget(
@@ -101,12 +150,48 @@ pub fn render(app: &mut App, frame: &mut Frame) {
 }
 
 fn render_title_bar(frame: &mut Frame, app: &App) {
-    let path = &app.focus_dir.path;
+    let title = match (&app.trash_view, &app.grep, &app.usage) {
+        (Some(_), _, _) => "Trash".to_string(),
+        (None, Some(grep), _) => format!("Grep \"{}\" in {}", grep.pattern, grep.root.display()),
+        (None, None, Some(usage)) => match usage.current() {
+            Some(dir) => {
+                let path = usage.current_path();
+                format!(
+                    "Disk usage of {} ({})",
+                    path.display(),
+                    human_size(dir.size)
+                )
+            }
+            None => format!("Disk usage of {}", usage.root.display()),
+        },
+        (None, None, None) => app.focus_dir.path.to_string_lossy().into_owned(),
+    };
+
+    let mut spans = vec![Span::raw(format!("— {} ", title))];
+    let repo = app
+        .git
+        .status
+        .as_ref()
+        .filter(|s| app.focus_dir.path.starts_with(&s.workdir));
+    let explorer = app.trash_view.is_none() && app.grep.is_none() && app.usage.is_none();
+    if let Some(repo) = repo.filter(|_| explorer) {
+        let mut branch = repo.branch.clone();
+        if repo.ahead > 0 {
+            branch += &format!(" ↑{}", repo.ahead);
+        }
+        if repo.behind > 0 {
+            branch += &format!(" ↓{}", repo.behind);
+        }
+        spans.push(Span::styled(
+            format!("{} ", branch),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
 
     let p = Paragraph::new("")
         .block(
             Block::default()
-                .title(format!("— {} ", path.to_string_lossy().into_owned()))
+                .title(Line::from(spans))
                 .borders(Borders::TOP),
         )
         .style(Style::default().fg(Color::Cyan));
@@ -115,14 +200,41 @@ fn render_title_bar(frame: &mut Frame, app: &App) {
 }
 
 fn render_info_bar(frame: &mut Frame, app: &App) {
-    let scroll_pos = match &app.app_cursor {
-        Some(c) => c.idx,
-        None => 0,
+    let scroll_pos = match (&app.trash_view, &app.app_cursor) {
+        (Some(view), _) => view.idx,
+        (None, Some(c)) => c.idx,
+        (None, None) => 0,
+    };
+    let register = match (app.register.kind, app.register.paths.len()) {
+        (_, 0) => String::new(),
+        (RegisterKind::Copy, n) => format!("[{} yanked] ", n),
+        (RegisterKind::Cut, n) => format!("[{} cut] ", n),
+    };
+    let filter = match &app.focus_dir.filter {
+        Some(filter) => format!("[filter: {}] ", filter),
+        None => String::new(),
+    };
+    let sorter = app.focus_dir.sorter;
+    let sort = match (sorter == app.options.sort, sorter.reverse) {
+        (true, _) => String::new(),
+        (false, false) => format!("[{}] ", sorter.name()),
+        (false, true) => format!("[{}, reversed] ", sorter.name()),
+    };
+    let hidden = match app.focus_dir.hidden {
+        0 => String::new(),
+        n => format!("[{} hidden] ", n),
+    };
+    let sizing = match app.du.progress() {
+        Some((done, total)) => format!("[sizing {}/{}] ", done, total),
+        None => String::new(),
     };
     let p = Paragraph::new("")
         .block(
             Block::default()
-                .title(format!("{} | {}", scroll_pos, app.focus_layout.height))
+                .title(format!(
+                    "{}{}{}{}{}{} | {}",
+                    sizing, hidden, sort, filter, register, scroll_pos, app.focus_layout.height
+                ))
                 .title_alignment(Alignment::Right)
                 .borders(Borders::TOP),
         )
@@ -137,13 +249,9 @@ fn render_parent_pane(frame: &mut Frame, app: &App) {
         |dir| {
             dir.contents
                 .iter()
-                .map(|path| {
-                    format_line(
-                        app,
-                        path.to_path_buf(),
-                        app.parent_layout.width.into(),
-                        PaneContext::Parent,
-                    )
+                .map(|entry| {
+                    let width = app.parent_layout.width.into();
+                    format_line(app, entry, width, PaneContext::Parent, None)
                 })
                 .collect()
         },
@@ -157,12 +265,24 @@ fn render_parent_pane(frame: &mut Frame, app: &App) {
 }
 
 fn render_focus_pane(frame: &mut Frame, app: &App) {
-    let width = app.focus_layout.width.into();
+    // inside the padding
+    let width = app.focus_layout.width.saturating_sub(2).into();
+    let details = app.options.details.then(|| {
+        let (columns, time_format) = (&app.options.columns, app.options.time_format.as_str());
+        Details::fit(
+            columns,
+            &app.focus_dir.contents,
+            width,
+            &app.names,
+            time_format,
+        )
+    });
+    let details = details.as_ref().filter(|d| !d.is_empty());
     let paths: Vec<Line> = app
         .focus_dir
         .contents
         .iter()
-        .map(|path| format_line(app, path.to_path_buf(), width, PaneContext::Focus))
+        .map(|entry| format_line(app, entry, width, PaneContext::Focus, details))
         .collect();
 
     let display = if paths.is_empty() {
@@ -214,68 +334,576 @@ fn render_focus_pane(frame: &mut Frame, app: &App) {
     // );
 }
 
-fn render_preview_pane(frame: &mut Frame, app: &App) {
-    let width = app.preview_layout.width.into();
-    let preview = match &app.app_cursor {
-        Some(cursor) => {
-            let selected = Path::new(&cursor.entry);
-            if selected.is_dir() {
-                let sub_paths: Vec<Line> = match fs::read_dir(selected) {
-                    Ok(dir) => {
-                        let mut entries: Vec<_> = dir.filter_map(|p| p.ok()).collect();
-
-                        entries.sort_by(|a, b| SORTER.entries(a, b));
-
-                        entries
-                            .into_iter()
-                            .map(|entry| {
-                                format_line(app, entry.path(), width, PaneContext::Preview)
-                            })
-                            .collect()
-                    }
-                    Err(err) => match err.kind() {
-                        io::ErrorKind::PermissionDenied => {
-                            vec![Line::from("Permission denied!".to_string())]
-                        }
-                        _ => vec![Line::from("Unknown error".to_string())],
-                    },
+fn render_buffer_pane(frame: &mut Frame, app: &App, buffer: &DirBuffer) {
+    let width = app.focus_layout.width.saturating_sub(2) as usize;
+    let height = app.focus_layout.height as usize;
+
+    let lines: Vec<Line> = buffer
+        .lines
+        .iter()
+        .map(|line| {
+            let fg_color = if line.is_new() {
+                Color::Green
+            } else if buffer.is_changed(line) {
+                Color::Yellow
+            } else if line.text.ends_with('/') {
+                Color::LightMagenta
+            } else {
+                Color::White
+            };
+            Line::from(format!("{:<width$}", line.text)).style(Style::default().fg(fg_color))
+        })
+        .collect();
+
+    let scroll_pos = buffer
+        .row
+        .saturating_sub(height / 2)
+        .min(lines.len().saturating_sub(height));
+
+    frame.render_widget(
+        Paragraph::new(Text::from(lines))
+            .block(Block::default().padding(Padding::symmetric(1, 0)))
+            .scroll((scroll_pos as u16, 0)),
+        app.focus_layout,
+    );
+
+    if buffer.mode != BufferMode::Command && buffer.plan.is_none() {
+        let x = app.focus_layout.x + 1 + buffer.col.min(width) as u16;
+        let y = app.focus_layout.y + (buffer.row - scroll_pos) as u16;
+        frame.set_cursor_position(Position::new(x, y));
+    }
+}
+
+fn render_buffer_status(frame: &mut Frame, app: &App, buffer: &DirBuffer) {
+    let status = match buffer.mode {
+        BufferMode::Normal if buffer.is_modified() => "-- EDIT -- [+]".to_string(),
+        BufferMode::Normal => "-- EDIT --".to_string(),
+        BufferMode::Insert => "-- INSERT --".to_string(),
+        BufferMode::Command => format!(":{}", buffer.command.content),
+    };
+
+    frame.render_widget(
+        Paragraph::new(Text::from(status))
+            .block(Block::default().borders(Borders::TOP))
+            .style(Style::default().fg(Color::Cyan)),
+        app.message_layout,
+    );
+
+    if buffer.mode == BufferMode::Command {
+        frame.set_cursor_position(Position::new(
+            app.message_layout.x + 1 + buffer.command.char_idx as u16,
+            app.message_layout.y + 1,
+        ));
+    }
+}
+
+fn render_plan(frame: &mut Frame, app: &App, buffer: &DirBuffer, plan: &[Change]) {
+    let lines: Vec<Line> = plan
+        .iter()
+        .map(|change| {
+            let fg_color = match change {
+                Change::Create { .. } => Color::Green,
+                Change::Move { .. } => Color::Yellow,
+                Change::Copy { .. } => Color::Blue,
+                Change::Delete { .. } => Color::Red,
+            };
+            Line::from(change.describe(&buffer.dir)).style(Style::default().fg(fg_color))
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(Text::from(lines)).wrap(Wrap { trim: false }),
+        app.preview_layout,
+    );
+}
+
+fn render_jobs_view(frame: &mut Frame, app: &App, selected: usize) {
+    let lines: Vec<Line> = if app.jobs.is_empty() {
+        vec![Line::from("No jobs running").style(Style::default().fg(Color::DarkGray))]
+    } else {
+        app.jobs
+            .jobs
+            .iter()
+            .enumerate()
+            .map(|(idx, job)| {
+                let style = match (idx == selected, job.is_cancelled()) {
+                    (true, _) => Style::default().fg(Color::Black).bg(Color::White),
+                    (false, true) => Style::default().fg(Color::DarkGray),
+                    (false, false) => Style::default().fg(Color::White),
                 };
+                Line::from(format_job(job)).style(style)
+            })
+            .collect()
+    };
 
-                if sub_paths.is_empty() {
-                    Text::from("Empty...").style(Style::default().fg(Color::Red).bg(Color::Black))
-                } else {
-                    Text::from(sub_paths)
-                    // sub_paths.sort();
-                    // sub_paths.join("\n")
-                }
-            } else if selected.is_file() {
-                if selected
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .map_or(false, |e| e.eq_ignore_ascii_case("pdf"))
-                {
-                    let doc = Document::load(selected);
-                    match doc {
-                        Ok(document) => {
-                            let text = document
-                                .extract_text(&[1])
-                                .unwrap_or("Failed to parse PDF".to_string());
-                            Text::from(text).style(Style::default().fg(Color::White))
-                        }
-                        Err(err) => Text::from(format!("{}", err)),
-                    }
+    frame.render_widget(
+        Paragraph::new(Text::from(lines))
+            .block(
+                Block::default()
+                    .title("Jobs (c cancel, q close)")
+                    .borders(Borders::TOP),
+            )
+            .wrap(Wrap { trim: false })
+            .style(Style::default().fg(Color::Cyan)),
+        app.preview_layout,
+    );
+}
+
+fn render_grep_results(frame: &mut Frame, app: &App, grep: &Grep) {
+    let list_area = app.parent_layout.union(app.focus_layout);
+    let width = list_area.width.saturating_sub(2) as usize;
+    let height = list_area.height.saturating_sub(1) as usize;
+    let offset = grep
+        .selected
+        .saturating_sub(height / 2)
+        .min(grep.hits.len().saturating_sub(height));
+
+    let lines: Vec<Line> = grep
+        .hits
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(idx, hit)| {
+            let selected = idx == grep.selected;
+            let style = |fg: Color| {
+                if selected {
+                    Style::default().fg(Color::Black).bg(fg)
                 } else {
-                    Text::from(
-                        fs::read_to_string(selected)
-                            .unwrap_or_else(|_| "Failed to parse whatever that is".to_string()),
-                    )
-                    .style(Style::default().fg(Color::White))
+                    Style::default().fg(fg)
                 }
+            };
+            let path = hit.path.strip_prefix(&grep.root).unwrap_or(&hit.path);
+            let location = format!("{}:{}: ", path.display(), hit.line);
+            let snippet = &hit.snippet;
+            let m = &hit.matched;
+            let used = location.chars().count() + snippet.chars().count();
+
+            Line::from(vec![
+                Span::styled(location, style(Color::LightMagenta)),
+                Span::styled(snippet[..m.start].to_string(), style(Color::White)),
+                Span::styled(
+                    snippet[m.clone()].to_string(),
+                    style(Color::LightYellow).add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(snippet[m.end..].to_string(), style(Color::White)),
+                Span::styled(" ".repeat(width.saturating_sub(used)), style(Color::White)),
+            ])
+        })
+        .collect();
+
+    let status = match grep.done {
+        Some(files) => format!("{} matches in {} files", grep.hits.len(), files),
+        None => format!("{} matches…", grep.hits.len()),
+    };
+    let display = if lines.is_empty() && grep.done.is_some() {
+        Text::from("No matches").style(Style::default().fg(Color::Red))
+    } else {
+        Text::from(lines)
+    };
+
+    frame.render_widget(
+        Paragraph::new(display).block(
+            Block::default()
+                .title(status)
+                .padding(Padding::symmetric(1, 0))
+                .style(Style::default().fg(Color::Cyan)),
+        ),
+        list_area,
+    );
+}
+
+/// The file around the selected hit, with the hit's line marked.
+fn render_grep_preview(frame: &mut Frame, app: &App, grep: &Grep) {
+    if grep.selected_hit().is_none() {
+        return;
+    }
+    let preview = match app.preview.current() {
+        Some(Content::Text(text)) => text.clone(),
+        _ => Text::from("Loading...").style(Style::default().fg(Color::DarkGray)),
+    };
+
+    frame.render_widget(Paragraph::new(preview), app.preview_layout);
+}
+
+/// Which-key style list at the bottom of the focus pane.
+fn render_sort_menu(frame: &mut Frame, app: &App) {
+    let current = app.focus_dir.sorter;
+    let mut lines: Vec<Line> = sorter::KEYS
+        .iter()
+        .map(|(key, name, c)| {
+            let style = if *key == current.key {
+                Style::default().fg(Color::Yellow)
             } else {
-                Text::from("Unkwnown entry type")
-                    .style(Style::default().fg(Color::Red).bg(Color::Black))
-            }
+                Style::default().fg(Color::White)
+            };
+            Line::styled(format!(" {}  {}", c, name), style)
+        })
+        .collect();
+    let reverse = if current.reverse { "on" } else { "off" };
+    let style = Style::default().fg(Color::White);
+    lines.push(Line::styled(format!(" r  reverse ({})", reverse), style));
+
+    let area = app.focus_layout;
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let width = area.width.min(24);
+    let popup = Rect::new(area.x, area.y + area.height - height, width, height);
+    let block = Block::default()
+        .title("— Sort ")
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Cyan));
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+fn render_finder(frame: &mut Frame, finder: &Finder) {
+    let area = frame.area();
+    let width = area.width * 4 / 5;
+    let height = area.height * 4 / 5;
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    let busy = if finder.is_busy() { "…" } else { "" };
+    let block = Block::default()
+        .title(format!("— Find in {} ", finder.root.display()))
+        .title(
+            Title::from(format!(
+                " {}/{}{} ",
+                finder.ranked.len(),
+                finder.items.len(),
+                busy
+            ))
+            .alignment(Alignment::Right),
+        )
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+
+    let prompt = "> ";
+    frame.render_widget(
+        Paragraph::new(format!("{}{}", prompt, finder.query.content))
+            .style(Style::default().fg(Color::White)),
+        Rect::new(inner.x, inner.y, inner.width, 1),
+    );
+    frame.set_cursor_position(Position::new(
+        inner.x + (prompt.len() + finder.query.char_idx) as u16,
+        inner.y,
+    ));
+
+    let list_height = inner.height.saturating_sub(1) as usize;
+    // keep the selection in view
+    let offset = (finder.selected + 1).saturating_sub(list_height);
+    let lines: Vec<Line> = finder
+        .ranked
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(list_height)
+        .map(|(idx, m)| {
+            let item = finder.items.get(m.item);
+            let base = match (idx == finder.selected, item.text.ends_with('/')) {
+                (true, _) => Style::default().fg(Color::Black).bg(Color::White),
+                (false, true) => Style::default().fg(Color::LightMagenta),
+                (false, false) => Style::default().fg(Color::White),
+            };
+            let matched = if idx == finder.selected {
+                base.fg(Color::Magenta).add_modifier(Modifier::BOLD)
+            } else {
+                base.fg(Color::LightYellow).add_modifier(Modifier::BOLD)
+            };
+            let indices = finder.indices(m);
+            let mut spans: Vec<Span> = item
+                .text
+                .chars()
+                .enumerate()
+                .map(|(i, ch)| {
+                    let style = if indices.contains(&i) { matched } else { base };
+                    Span::styled(ch.to_string(), style)
+                })
+                .collect();
+            let pad = (inner.width as usize).saturating_sub(item.text.chars().count());
+            spans.push(Span::styled(" ".repeat(pad), base));
+            Line::from(spans)
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(Text::from(lines)),
+        Rect::new(
+            inner.x,
+            inner.y + 1,
+            inner.width,
+            inner.height.saturating_sub(1),
+        ),
+    );
+}
+
+fn format_job(job: &Job) -> String {
+    let p = &job.progress;
+    let mut line = format!(
+        "{} {}: {}/{} files",
+        job.verb, job.label, p.files_done, p.files_total
+    );
+    if p.bytes_total > 0 {
+        line += &format!(
+            ", {}/{}",
+            human_size(p.bytes_done),
+            human_size(p.bytes_total)
+        );
+    }
+    if let Some(eta) = p.eta(job.started.elapsed()) {
+        line += &format!(", ETA {}s", eta.as_secs() + 1);
+    }
+    if job.is_cancelled() {
+        line += " [cancelling]";
+    }
+    line
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", bytes, UNITS[0]),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+fn render_trash_view(frame: &mut Frame, app: &App) {
+    let Some(view) = &app.trash_view else {
+        return;
+    };
+
+    let list_area = app.parent_layout.union(app.focus_layout);
+    let width = list_area.width.saturating_sub(2) as usize;
+    let lines: Vec<Line> = view
+        .entries
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            let fg_color = if entry.trashed.is_dir() {
+                Color::LightMagenta
+            } else {
+                Color::White
+            };
+            let style = if idx == view.idx {
+                Style::default().fg(Color::Black).bg(fg_color)
+            } else {
+                Style::default().fg(fg_color)
+            };
+
+            Line::from(format!("{:<width$}", entry.name.to_string_lossy())).style(style)
+        })
+        .collect();
+
+    let display = if lines.is_empty() {
+        Text::from(format!("{:<width$}", "The trash is empty"))
+            .style(Style::default().fg(Color::Red).bg(Color::Black))
+    } else {
+        Text::from(lines)
+    };
+
+    let height = list_area.height as usize;
+    let scroll_pos = view
+        .idx
+        .saturating_sub(height / 2)
+        .min(display.lines.len().saturating_sub(height));
+
+    frame.render_widget(
+        Paragraph::new(display)
+            .block(Block::default().padding(Padding::symmetric(1, 0)))
+            .scroll((scroll_pos as u16, 0)),
+        list_area,
+    );
+
+    let details = match view.selected() {
+        Some(entry) => Text::from(vec![
+            Line::from(format!("Original path: {}", entry.original.display())),
+            Line::from(format!(
+                "Deleted at:    {}",
+                entry
+                    .deleted
+                    .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+            )),
+            Line::from(""),
+            Line::from("r restore  d purge  q close").style(Style::default().fg(Color::DarkGray)),
+        ]),
+        None => Text::from(""),
+    };
+
+    frame.render_widget(
+        Paragraph::new(details)
+            .wrap(Wrap { trim: false })
+            .style(Style::default().fg(Color::White)),
+        app.preview_layout,
+    );
+}
+
+/// The explorer panes over the scanned tree: where the current directory sits
+/// in its parent, its entries ranked by size, and what's in the selected one.
+fn render_usage(frame: &mut Frame, app: &App, usage: &Usage) {
+    let Some(dir) = usage.current() else {
+        let (files, bytes) = usage.progress;
+        frame.render_widget(
+            Paragraph::new(format!("Scanning… {} files, {}", files, human_size(bytes)))
+                .block(Block::default().padding(Padding::symmetric(1, 0)))
+                .style(Style::default().fg(Color::DarkGray)),
+            app.focus_layout,
+        );
+        return;
+    };
+
+    if let Some((parent, idx)) = usage.parent() {
+        let width = app.parent_layout.width.into();
+        let lines = usage_names(parent, Some(idx), width, app.parent_layout.height.into());
+        frame.render_widget(Paragraph::new(Text::from(lines)), app.parent_layout);
+    }
+
+    let width = app.focus_layout.width.saturating_sub(2) as usize;
+    let height = app.focus_layout.height as usize;
+    let largest = dir.children.first().map_or(0, |c| c.size);
+    let offset = usage
+        .selected
+        .saturating_sub(height / 2)
+        .min(dir.children.len().saturating_sub(height));
+    let lines: Vec<Line> = dir
+        .children
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(idx, node)| {
+            let percent = match dir.size {
+                0 => 0.0,
+                total => node.size as f64 * 100.0 / total as f64,
+            };
+            let filled = match largest {
+                0 => 0,
+                largest => (node.size as f64 / largest as f64 * BAR as f64).round() as usize,
+            };
+            let text = format!(
+                "{:>10} {:>5.1}% [{:<BAR$}] {}",
+                human_size(node.size),
+                percent,
+                "#".repeat(filled),
+                usage_name(node)
+            );
+            Line::from(format!("{:<width$}", text)).style(usage_style(node, idx == usage.selected))
+        })
+        .collect();
+    let display = if lines.is_empty() {
+        Text::from("Empty...").style(Style::default().fg(Color::Red).bg(Color::Black))
+    } else {
+        Text::from(lines)
+    };
+    frame.render_widget(
+        Paragraph::new(display).block(Block::default().padding(Padding::symmetric(1, 0))),
+        app.focus_layout,
+    );
+
+    let preview = match usage.selected_node() {
+        Some(node) if node.dir && node.children.is_empty() => {
+            Text::from("Empty...").style(Style::default().fg(Color::Red).bg(Color::Black))
         }
+        Some(node) if node.dir => {
+            let width = app.preview_layout.width.into();
+            Text::from(usage_names(
+                node,
+                None,
+                width,
+                app.preview_layout.height.into(),
+            ))
+        }
+        Some(node) => Text::from(vec![
+            Line::from(format!("{} ({} bytes)", human_size(node.size), node.size)),
+            Line::from(""),
+            Line::from("d trash  D delete  h/l out/in  q close")
+                .style(Style::default().fg(Color::DarkGray)),
+        ])
+        .style(Style::default().fg(Color::White)),
+        None => Text::from(""),
+    };
+    frame.render_widget(Paragraph::new(preview), app.preview_layout);
+}
+
+/// Sizes and names only, for the panes on either side.
+fn usage_names(
+    dir: &Node,
+    selected: Option<usize>,
+    width: usize,
+    height: usize,
+) -> Vec<Line<'static>> {
+    let offset = selected.map_or(0, |s| {
+        s.saturating_sub(height / 2)
+            .min(dir.children.len().saturating_sub(height))
+    });
+    dir.children
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(idx, node)| {
+            let text = format!("{:>10} {}", human_size(node.size), usage_name(node));
+            Line::from(format!("{:<width$}", text)).style(usage_style(node, selected == Some(idx)))
+        })
+        .collect()
+}
+
+fn usage_name(node: &Node) -> String {
+    let name = node.name.to_string_lossy();
+    match (node.dir, node.unreadable) {
+        (true, true) => format!("{}/ (unreadable)", name),
+        (true, false) => format!("{}/", name),
+        _ => name.into_owned(),
+    }
+}
+
+fn usage_style(node: &Node, selected: bool) -> Style {
+    let fg = match (node.dir, node.unreadable) {
+        (true, true) => Color::Red,
+        (true, false) => Color::LightMagenta,
+        _ => Color::White,
+    };
+    if selected {
+        Style::default().fg(Color::Black).bg(fg)
+    } else {
+        Style::default().fg(fg)
+    }
+}
+
+fn render_preview_pane(frame: &mut Frame, app: &App) {
+    let width = app.preview_layout.width.into();
+    let height = app.preview_layout.height.into();
+    let preview = match &app.app_cursor {
+        Some(_) => match app.preview.current() {
+            Some(Content::Dir(entries)) if entries.is_empty() => {
+                Text::from("Empty...").style(Style::default().fg(Color::Red).bg(Color::Black))
+            }
+            Some(Content::Dir(entries)) => {
+                let lines: Vec<Line> = entries
+                    .iter()
+                    .take(height)
+                    .map(|entry| format_line(app, entry, width, PaneContext::Preview, None))
+                    .collect();
+                Text::from(lines)
+            }
+            Some(Content::Text(text)) => text.clone(),
+            None => Text::from("Loading...").style(Style::default().fg(Color::DarkGray)),
+        },
         None => Text::from("nope"),
     };
 
@@ -292,32 +920,123 @@ enum PaneContext {
     Preview,
 }
 
-fn format_line(app: &App, path: PathBuf, width: usize, ctx: PaneContext) -> Line {
+fn format_line(
+    app: &App,
+    entry: &FileSystemEntry,
+    width: usize,
+    ctx: PaneContext,
+    details: Option<&Details>,
+) -> Line<'static> {
+    let path = &entry.path;
     let mut fg_color = Color::White;
     let mut bg_color = Color::Reset;
-    let mut basename = path.file_name().unwrap().to_string_lossy().into_owned();
-    if path.is_dir() {
+    let mut basename = entry.name().to_string_lossy().into_owned();
+    // where the name starts, after the selection mark if there's one
+    let mut name_start = 0;
+    if entry.is_dir() {
         fg_color = Color::LightMagenta;
-    } else if path.is_symlink() {
+    } else if entry.is_symlink() {
         fg_color = Color::Cyan;
     }
 
-    if app.selections.values().any(|set| set.contains(&path)) {
+    let git = app.git.get(path).map(git_badge);
+    if let Some((_, color)) = git {
+        fg_color = color;
+    }
+
+    if app.register.kind == RegisterKind::Cut && app.register.contains(path) {
+        fg_color = Color::DarkGray;
+    }
+
+    if app.selections.values().any(|set| set.contains(path)) {
         if matches!(ctx, PaneContext::Focus) {
             fg_color = Color::Yellow;
         }
         basename = MARK.to_owned() + &basename;
+        name_start = MARK.len();
     }
     if (matches!(ctx, PaneContext::Focus)
-        && app.app_cursor.as_ref().map(|c| &c.entry).eq(&Some(&path)))
-        || (matches!(ctx, PaneContext::Parent) && app.focus_dir.path == path)
-        || (matches!(ctx, PaneContext::Preview) && (app.forward_stack.last() == Some(&path)))
+        && app.app_cursor.as_ref().map(|c| &c.entry).eq(&Some(path)))
+        || (matches!(ctx, PaneContext::Parent) && &app.focus_dir.path == path)
+        || (matches!(ctx, PaneContext::Preview) && (app.forward_stack.last() == Some(path)))
     {
         bg_color = fg_color;
         fg_color = Color::Black;
     }
 
+    // the badge takes the last two columns, with details they're kept on
+    // every line so the columns line up
+    let badged = git.is_some() || (details.is_some() && app.git.status.is_some());
+    let badge_width = if badged { 2 } else { 0 };
+    let width = width.saturating_sub(badge_width + details.map_or(0, Details::width));
+    let mut cut = basename.len();
+    if details.is_some() && basename.chars().count() > width {
+        cut = basename
+            .char_indices()
+            .nth(width.saturating_sub(1))
+            .map_or(0, |(i, _)| i);
+        basename.truncate(cut);
+        basename.push('…');
+    }
     let padded_name = format!("{:<width$}", basename);
     let style = Style::default().fg(fg_color).bg(bg_color);
-    Line::from(padded_name).style(style)
+
+    let matched = match (&ctx, &app.pattern) {
+        (PaneContext::Focus, Some(pattern)) => {
+            let name = entry.name().to_string_lossy();
+            let name_start = name_start.min(cut);
+            search::find(&name, pattern)
+                .map(|m| (name_start + m.start).min(cut)..(name_start + m.end).min(cut))
+                .filter(|m| !m.is_empty())
+        }
+        _ => None,
+    };
+
+    let mut line = match matched {
+        Some(m) => {
+            let highlight = match bg_color {
+                Color::Reset => Style::default().fg(Color::Black).bg(Color::LightYellow),
+                _ => style.add_modifier(Modifier::UNDERLINED | Modifier::BOLD),
+            };
+            Line::from(vec![
+                Span::styled(padded_name[..m.start].to_string(), style),
+                Span::styled(padded_name[m.clone()].to_string(), highlight),
+                Span::styled(padded_name[m.end..].to_string(), style),
+            ])
+        }
+        None => Line::from(padded_name).style(style),
+    };
+
+    if let Some(details) = details {
+        let details_style = match bg_color {
+            Color::Reset => Style::default().fg(Color::DarkGray),
+            _ => style,
+        };
+        line.spans
+            .push(Span::styled(details.cells(entry), details_style));
+    }
+    match git {
+        Some((badge, color)) => {
+            // on the cursor the line is already in the status' colour
+            let badge_style = match bg_color {
+                Color::Reset => Style::default().fg(color),
+                _ => style,
+            };
+            line.spans
+                .push(Span::styled(format!(" {}", badge), badge_style));
+        }
+        None if badge_width > 0 => line.spans.push(Span::styled("  ", style)),
+        None => {}
+    }
+    line
+}
+
+fn git_badge(status: GitStatus) -> (char, Color) {
+    match status {
+        GitStatus::Ignored => ('!', Color::DarkGray),
+        GitStatus::Untracked => ('?', Color::LightRed),
+        GitStatus::Staged => ('+', Color::Green),
+        GitStatus::Modified => ('M', Color::Yellow),
+        GitStatus::Conflicted => ('U', Color::Red),
+    }
 }
@@ -0,0 +1,313 @@
+// scans a subtree on the blocking pool into a tree of sizes, like ncdu. The
+// whole tree is kept so walking in and out of it never touches the disk again
+
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::HashSet,
+    ffi::OsString,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::event::Event;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub enum UsageEvent {
+    Progress { id: usize, files: u64, bytes: u64 },
+    Done { id: usize, tree: Node },
+}
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: OsString,
+    /// Everything below it for directories.
+    pub size: u64,
+    pub dir: bool,
+    /// Couldn't be read, whatever is in there isn't counted.
+    pub unreadable: bool,
+    /// Biggest first.
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug)]
+pub struct Usage {
+    id: usize,
+    pub root: PathBuf,
+    /// `None` while scanning.
+    pub tree: Option<Node>,
+    /// Files and bytes counted so far.
+    pub progress: (u64, u64),
+    /// Indices of the directories walked into, from the root down.
+    trail: Vec<usize>,
+    pub selected: usize,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Usage {
+    /// Starts scanning right away.
+    pub fn new(root: PathBuf, sender: UnboundedSender<Event>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let (scan_root, scan_cancel) = (root.clone(), cancel.clone());
+        tokio::task::spawn_blocking(move || scan(id, &scan_root, &sender, &scan_cancel));
+
+        Self {
+            id,
+            root,
+            tree: None,
+            progress: (0, 0),
+            trail: Vec::new(),
+            selected: 0,
+            cancel,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: UsageEvent) {
+        match event {
+            UsageEvent::Progress { id, files, bytes } if id == self.id => {
+                self.progress = (files, bytes)
+            }
+            UsageEvent::Done { id, tree } if id == self.id => self.tree = Some(tree),
+            _ => {}
+        }
+    }
+
+    /// The directory being shown.
+    pub fn current(&self) -> Option<&Node> {
+        let mut node = self.tree.as_ref()?;
+        for idx in &self.trail {
+            node = &node.children[*idx];
+        }
+        Some(node)
+    }
+
+    /// The one above the one being shown, and where that is in it.
+    pub fn parent(&self) -> Option<(&Node, usize)> {
+        let (last, above) = self.trail.split_last()?;
+        let mut node = self.tree.as_ref()?;
+        for idx in above {
+            node = &node.children[*idx];
+        }
+        Some((node, *last))
+    }
+
+    pub fn selected_node(&self) -> Option<&Node> {
+        self.current()?.children.get(self.selected)
+    }
+
+    pub fn current_path(&self) -> PathBuf {
+        let mut path = self.root.clone();
+        let mut node = self.tree.as_ref();
+        for idx in &self.trail {
+            node = node.map(|n| &n.children[*idx]);
+            path.extend(node.map(|n| &n.name));
+        }
+        path
+    }
+
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        Some(self.current_path().join(&self.selected_node()?.name))
+    }
+
+    pub fn move_up(&mut self, wrap: bool) {
+        let len = self.current().map_or(0, |n| n.children.len());
+        if self.selected > 0 {
+            self.selected -= 1;
+        } else if wrap {
+            self.selected = len.saturating_sub(1);
+        }
+    }
+
+    pub fn move_down(&mut self, wrap: bool) {
+        let len = self.current().map_or(0, |n| n.children.len());
+        if self.selected + 1 < len {
+            self.selected += 1;
+        } else if wrap {
+            self.selected = 0;
+        }
+    }
+
+    /// Walks into the selected directory, false if it isn't one.
+    pub fn enter(&mut self) -> bool {
+        if !self.selected_node().is_some_and(|n| n.dir) {
+            return false;
+        }
+        self.trail.push(self.selected);
+        self.selected = 0;
+        true
+    }
+
+    /// Walks back out with the cursor on where it was, false at the root.
+    pub fn leave(&mut self) -> bool {
+        match self.trail.pop() {
+            Some(idx) => {
+                self.selected = idx;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes the selected entry out of the tree and its size off every
+    /// directory above it, once it's been deleted.
+    pub fn remove_selected(&mut self) {
+        let Some(size) = self.selected_node().map(|n| n.size) else {
+            return;
+        };
+        let Some(mut node) = self.tree.as_mut() else {
+            return;
+        };
+
+        node.size -= size;
+        for idx in &self.trail {
+            node = &mut node.children[*idx];
+            node.size -= size;
+        }
+        node.children.remove(self.selected);
+        self.selected = self.selected.min(node.children.len().saturating_sub(1));
+        self.rerank();
+    }
+
+    /// The directories above shrank and may have dropped below their
+    /// siblings, the trail follows them to where they went.
+    fn rerank(&mut self) {
+        let Some(mut node) = self.tree.as_mut() else {
+            return;
+        };
+        for idx in self.trail.iter_mut() {
+            let name = node.children[*idx].name.clone();
+            node.children.sort_by(rank);
+            *idx = node
+                .children
+                .iter()
+                .position(|c| c.name == name)
+                .unwrap_or_default();
+            node = &mut node.children[*idx];
+        }
+    }
+}
+
+impl Drop for Usage {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn rank(a: &Node, b: &Node) -> CmpOrdering {
+    b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name))
+}
+
+struct Scan<'a> {
+    id: usize,
+    dev: u64,
+    /// Hard links are only counted the first time.
+    seen: HashSet<u64>,
+    files: u64,
+    bytes: u64,
+    last_report: Instant,
+    sender: &'a UnboundedSender<Event>,
+    cancel: &'a AtomicBool,
+}
+
+/// Sizes are apparent sizes and other filesystems are left alone, like
+/// `du -sbx` and the directory sizes in the panes.
+fn scan(id: usize, root: &Path, sender: &UnboundedSender<Event>, cancel: &AtomicBool) {
+    let Ok(meta) = fs::symlink_metadata(root) else {
+        return;
+    };
+    let mut scan = Scan {
+        id,
+        dev: meta.dev(),
+        seen: HashSet::new(),
+        files: 0,
+        bytes: 0,
+        last_report: Instant::now(),
+        sender,
+        cancel,
+    };
+
+    let name = root.as_os_str().to_os_string();
+    if let Some(tree) = scan.dir(root, name, meta.len()) {
+        let _ = sender.send(Event::Usage(UsageEvent::Done { id, tree }));
+    }
+}
+
+impl Scan<'_> {
+    /// `None` once cancelled.
+    fn dir(&mut self, path: &Path, name: OsString, size: u64) -> Option<Node> {
+        let mut node = Node {
+            name,
+            size,
+            dir: true,
+            unreadable: false,
+            children: Vec::new(),
+        };
+        let Ok(entries) = fs::read_dir(path) else {
+            node.unreadable = true;
+            return Some(node);
+        };
+
+        for entry in entries.flatten() {
+            if self.cancel.load(Ordering::Relaxed) {
+                return None;
+            }
+            // doesn't follow symlinks
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.dev() != self.dev {
+                continue;
+            }
+            if meta.nlink() > 1 && !meta.is_dir() && !self.seen.insert(meta.ino()) {
+                continue;
+            }
+
+            self.files += 1;
+            self.bytes += meta.len();
+            self.report();
+
+            let child = if meta.is_dir() {
+                self.dir(&entry.path(), entry.file_name(), meta.len())?
+            } else {
+                Node {
+                    name: entry.file_name(),
+                    size: meta.len(),
+                    dir: false,
+                    unreadable: false,
+                    children: Vec::new(),
+                }
+            };
+            node.size += child.size;
+            node.children.push(child);
+        }
+
+        node.children.sort_by(rank);
+        Some(node)
+    }
+
+    fn report(&mut self) {
+        if self.last_report.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last_report = Instant::now();
+        let event = UsageEvent::Progress {
+            id: self.id,
+            files: self.files,
+            bytes: self.bytes,
+        };
+        let _ = self.sender.send(Event::Usage(event));
+    }
+}
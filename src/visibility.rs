@@ -0,0 +1,96 @@
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::{config::Options, filesystem_entry::FileSystemEntry};
+
+/// Decides what's left out of a listing. The parent, focus and preview panes
+/// all go through the same one, so a directory looks the same in each.
+#[derive(Debug, Default)]
+pub struct Visibility {
+    /// Shows everything, dotfiles and ignored entries alike.
+    show_hidden: bool,
+    globs: GlobSet,
+    gitignore: bool,
+}
+
+impl Visibility {
+    pub fn new(options: &Options) -> Result<Self, String> {
+        Ok(Self {
+            show_hidden: options.hidden,
+            globs: build_globs(&options.ignore)?,
+            gitignore: options.gitignore,
+        })
+    }
+
+    /// Drops whatever shouldn't be shown from a listing of `dir` and returns
+    /// how many went. Archives have no ignore files to go by.
+    pub fn apply(&self, dir: &Path, entries: &mut Vec<FileSystemEntry>, on_disk: bool) -> usize {
+        if self.show_hidden {
+            return 0;
+        }
+
+        let ignores = if self.gitignore && on_disk {
+            ignore_files(dir)
+        } else {
+            Vec::new()
+        };
+        let before = entries.len();
+        entries.retain(|e| {
+            let name = e.name();
+            !(name.to_string_lossy().starts_with('.')
+                || self.globs.is_match(name)
+                || is_ignored(&ignores, &e.path, e.is_dir()))
+        });
+        before - entries.len()
+    }
+}
+
+/// Patterns are matched against names only, so `target` hides every
+/// `target` and `*.pyc` every compiled Python file.
+pub fn build_globs(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| e.to_string())?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// `.gitignore` and `.ignore` files from `dir` up to the root of its
+/// repository, closest first. Each one's patterns are relative to where it
+/// sits, so they can't be merged into one.
+fn ignore_files(dir: &Path) -> Vec<Gitignore> {
+    let mut ignores = Vec::new();
+    for ancestor in dir.ancestors() {
+        let mut builder = GitignoreBuilder::new(ancestor);
+        let mut found = false;
+        // .ignore goes last so it wins, like in ripgrep
+        for name in [".gitignore", ".ignore"] {
+            let file = ancestor.join(name);
+            if file.is_file() {
+                // a bad line only loses that line
+                builder.add(file);
+                found = true;
+            }
+        }
+        if let Some(ignore) = found.then(|| builder.build().ok()).flatten() {
+            ignores.push(ignore);
+        }
+        if ancestor.join(".git").exists() {
+            break;
+        }
+    }
+    ignores
+}
+
+/// The closest file with something to say about `path` decides, a `!pattern`
+/// there brings back what a file further up hid.
+fn is_ignored(ignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .map(|ignore| ignore.matched(path, is_dir))
+        .find(|m| !m.is_none())
+        .is_some_and(|m| m.is_ignore())
+}
@@ -0,0 +1,137 @@
+// keeps the listings fresh with inotify (or whatever notify picks elsewhere)
+// instead of re-reading them on every tick. Directories that can't be
+// watched, like on some network mounts, are polled instead
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use notify::{
+    event::{MetadataKind, ModifyKind},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher as _,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::event::Event;
+
+/// Changes are collected for this long before the app hears about them, so
+/// a copy of a thousand files doesn't mean a thousand re-reads.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub struct Watcher {
+    /// `None` if inotify couldn't even be set up, everything is polled then.
+    watcher: Option<RecommendedWatcher>,
+    watched: HashSet<PathBuf>,
+    /// The ones watching didn't work for.
+    polled: HashSet<PathBuf>,
+}
+
+impl Watcher {
+    pub fn new(sender: UnboundedSender<Event>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                let _ = tx.send(event);
+            }
+        })
+        .ok();
+        if watcher.is_some() {
+            std::thread::spawn(move || debounce(rx, sender));
+        }
+
+        Self {
+            watcher,
+            watched: HashSet::new(),
+            polled: HashSet::new(),
+        }
+    }
+
+    /// Watches exactly `dirs`, dropping the watches on anything else.
+    pub fn watch(&mut self, dirs: &[&Path]) {
+        let same = dirs.len() == self.watched.len() + self.polled.len()
+            && dirs
+                .iter()
+                .all(|d| self.watched.contains(*d) || self.polled.contains(*d));
+        if same {
+            return;
+        }
+
+        self.polled.retain(|p| dirs.contains(&p.as_path()));
+        let gone: Vec<_> = self
+            .watched
+            .iter()
+            .filter(|p| !dirs.contains(&p.as_path()))
+            .cloned()
+            .collect();
+        for dir in gone {
+            if let Some(watcher) = &mut self.watcher {
+                let _ = watcher.unwatch(&dir);
+            }
+            self.watched.remove(&dir);
+        }
+
+        for dir in dirs {
+            if self.watched.contains(*dir) || self.polled.contains(*dir) {
+                continue;
+            }
+            let watching = self
+                .watcher
+                .as_mut()
+                .is_some_and(|w| w.watch(dir, RecursiveMode::NonRecursive).is_ok());
+            let set = if watching {
+                &mut self.watched
+            } else {
+                &mut self.polled
+            };
+            set.insert(dir.to_path_buf());
+        }
+    }
+
+    /// Whether `dir` has to be re-read every so often.
+    pub fn is_polled(&self, dir: &Path) -> bool {
+        self.polled.contains(dir)
+    }
+}
+
+/// Turns raw events into batches of directories whose listings changed.
+fn debounce(rx: mpsc::Receiver<notify::Event>, sender: UnboundedSender<Event>) {
+    // gone once the watcher is dropped
+    while let Ok(event) = rx.recv() {
+        let mut paths = HashSet::new();
+        collect(event, &mut paths);
+
+        let deadline = Instant::now() + DEBOUNCE;
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            match rx.recv_timeout(left) {
+                Ok(event) => collect(event, &mut paths),
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        if !paths.is_empty() && sender.send(Event::FsChanged(paths)).is_err() {
+            return;
+        }
+    }
+}
+
+fn collect(event: notify::Event, paths: &mut HashSet<PathBuf>) {
+    // previews open and read files all the time, that changes nothing
+    match event.kind {
+        EventKind::Access(_) => return,
+        EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)) => return,
+        _ => {}
+    }
+    for path in event.paths {
+        if let Some(parent) = path.parent() {
+            paths.insert(parent.to_path_buf());
+        }
+        // the path itself too, for a watched directory going away or the
+        // file in the preview changing
+        paths.insert(path);
+    }
+}